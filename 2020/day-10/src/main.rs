@@ -1,8 +1,14 @@
 extern crate defaultmap;
 extern crate itertools;
+extern crate num_bigint;
+extern crate rand;
 
 use defaultmap::DefaultHashMap;
 use itertools::zip;
+use num_bigint::BigUint;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use std::env;
 use std::fs;
 use std::collections::HashMap;
 use std::vec::Vec;
@@ -13,7 +19,13 @@ fn numbers_from_contents(contents: &str) -> Vec<u32> {
         .collect()
 }
 
-fn get_sorted(input: &[u32]) -> Vec<u32> {
+#[derive(Debug, PartialEq)]
+enum ChainError {
+    // No adapter can take its input from anything at or below `from`.
+    Unreachable { from: u32, to: u32 }
+}
+
+fn get_sorted(input: &[u32], device_gap: u32) -> Vec<u32> {
     let mut sorted = input.to_vec();
 
     // Add the airplane socket before sorting; it will get sorted to
@@ -21,8 +33,17 @@ fn get_sorted(input: &[u32]) -> Vec<u32> {
     // when we know what the highest value is.
     sorted.push(0);
     sorted.sort();
-    sorted.push(sorted.last().unwrap() + 3);
-    return sorted;
+    sorted.push(sorted.last().unwrap() + device_gap);
+    sorted
+}
+
+// The error for a list with no arrangement: the first gap which can't be
+// crossed, or the last if duplicates left nothing to reach the device from.
+fn unreachable(sorted: &[u32], max_gap: u32) -> ChainError {
+    let index = (1..sorted.len())
+        .find(|&i| sorted[i] - sorted[i - 1] > max_gap)
+        .unwrap_or(sorted.len() - 1);
+    ChainError::Unreachable { from: sorted[index - 1], to: sorted[index] }
 }
 
 fn count_jolt_differences(sorted: &[u32]) -> HashMap<u32, u32> {
@@ -33,49 +54,183 @@ fn count_jolt_differences(sorted: &[u32]) -> HashMap<u32, u32> {
     counts.into()
 }
 
-fn count_jolt_permutations(sorted: &[u32]) -> u64 {
-    // Recursive, slow search. Do this for each run where there 
-    // are deltas of 1 and 2 between the items.
-    fn _count(list: &[u32]) -> u64 {
-        let upper = if list.len() > 4 { 4 }
-                    else if list.len() > 1 { list.len() }
-                    else { return 1; };
-        let mut options = 0;
-        for index in 1..upper {
-            if list[index] - list[0] > 3 {
+fn count_jolt_permutations(sorted: &[u32], max_gap: u32) -> Result<BigUint, ChainError> {
+    // ways[i] is the number of arrangements which end at sorted[i]; each
+    // adapter can follow any lower one within max_gap of it. Adapters of
+    // equal rating can't follow one another, so some may have no ways at
+    // all and just get skipped; only an empty total at the device is an error.
+    let mut ways: Vec<BigUint> = Vec::with_capacity(sorted.len());
+    ways.push(BigUint::from(1u32));
+    for index in 1..sorted.len() {
+        let mut total = BigUint::from(0u32);
+        for prev in (0..index).rev() {
+            let gap = sorted[index] - sorted[prev];
+            if gap > max_gap {
                 break;
+            } else if gap > 0 {
+                total += &ways[prev];
             }
-            options += _count(&list[index..]);
         }
-        return options;
+        ways.push(total);
     }
+    let total = ways.pop().unwrap();
+    if total == BigUint::from(0u32) {
+        return Err(unreachable(sorted, max_gap));
+    }
+    Ok(total)
+}
 
-    // Break up the overall sequence into runs by finding the diffs
-    // of three, run the recursive logic over those segments and get
-    // the product of all of it.
-    let mut perms = 1u64;
-    let mut base_index = 0;
-    while base_index < sorted.len() - 2 {
-        for index in base_index..(sorted.len() - 1) {
-            if sorted[index + 1] - sorted[index] == 3 {
-                perms *= _count(&sorted[base_index..(index + 1)]);
-                base_index = index + 1;
-                break;
+
+// Indices of the adapters which can directly follow sorted[index].
+fn successors(sorted: &[u32], max_gap: u32, index: usize) -> impl Iterator<Item = usize> + '_ {
+    (index + 1..sorted.len())
+        .take_while(move |&next| sorted[next] - sorted[index] <= max_gap)
+        .filter(move |&next| sorted[next] > sorted[index])
+}
+
+// Every valid arrangement of the adapters in a list from get_sorted, ordered
+// lexicographically by the indices used. Arrangements are returned as the
+// joltages of the chain, from the socket through to the device.
+struct Chains<'a> {
+    sorted: &'a [u32],
+    max_gap: u32,
+    // ways_from[i] is the number of ways to get from sorted[i] to the device.
+    ways_from: Vec<BigUint>
+}
+
+impl<'a> Chains<'a> {
+    fn new(sorted: &'a [u32], max_gap: u32) -> Result<Self, ChainError> {
+        let mut ways_from = vec![BigUint::from(0u32); sorted.len()];
+        ways_from[sorted.len() - 1] = BigUint::from(1u32);
+        for index in (0..sorted.len() - 1).rev() {
+            let total = successors(sorted, max_gap, index)
+                .fold(BigUint::from(0u32), |total, next| total + &ways_from[next]);
+            ways_from[index] = total;
+        }
+        if ways_from[0] == BigUint::from(0u32) {
+            return Err(unreachable(sorted, max_gap));
+        }
+        Ok(Self { sorted, max_gap, ways_from })
+    }
+
+    fn count(&self) -> &BigUint {
+        &self.ways_from[0]
+    }
+
+    fn iter(&self) -> ChainIter<'_, 'a> {
+        ChainIter { chains: self, path: Vec::new() }
+    }
+
+    fn to_joltages(&self, path: &[usize]) -> Vec<u32> {
+        path.iter().map(|&index| self.sorted[index]).collect()
+    }
+
+    // The k-th arrangement (from zero) in the same order as iter(), found by
+    // skipping over whole subtrees using the suffix counts.
+    fn nth(&self, k: &BigUint) -> Option<Vec<u32>> {
+        if k >= self.count() {
+            return None;
+        }
+        let mut k = k.clone();
+        let mut path = vec![0];
+        while *path.last().unwrap() != self.sorted.len() - 1 {
+            let index = *path.last().unwrap();
+            for next in successors(self.sorted, self.max_gap, index) {
+                if k < self.ways_from[next] {
+                    path.push(next);
+                    break;
+                }
+                k -= &self.ways_from[next];
             }
         }
+        Some(self.to_joltages(&path))
+    }
+
+    // One arrangement chosen uniformly at random.
+    fn sample<R: Rng>(&self, rng: &mut R) -> Vec<u32> {
+        // Rejection sample an index with as many bits as the count.
+        let bits = self.count().bits();
+        let mut bytes = vec![0u8; bits.div_ceil(8) as usize];
+        loop {
+            rng.fill(&mut bytes[..]);
+            if !bits.is_multiple_of(8) {
+                *bytes.last_mut().unwrap() &= (1u8 << (bits % 8)) - 1;
+            }
+            if let Some(chain) = self.nth(&BigUint::from_bytes_le(&bytes)) {
+                return chain;
+            }
+        }
+    }
+
+    // Extend a partial path with the lowest adapters which can still reach the device.
+    fn extend_lowest(&self, path: &mut Vec<usize>) {
+        while *path.last().unwrap() != self.sorted.len() - 1 {
+            let index = *path.last().unwrap();
+            let next = successors(self.sorted, self.max_gap, index)
+                .find(|&next| self.ways_from[next] > BigUint::from(0u32))
+                .unwrap();
+            path.push(next);
+        }
     }
-    return perms;
 }
 
+// A depth-first walk holding only the current path, so arrangements are
+// produced lazily however many there are.
+struct ChainIter<'c, 'a> {
+    chains: &'c Chains<'a>,
+    path: Vec<usize>
+}
+
+impl Iterator for ChainIter<'_, '_> {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Vec<u32>> {
+        let chains = self.chains;
+        if self.path.is_empty() {
+            self.path.push(0);
+        } else {
+            // Backtrack to the deepest step which has an unvisited sibling.
+            loop {
+                let last = self.path.pop().unwrap();
+                let index = *self.path.last()?;
+                let sibling = successors(chains.sorted, chains.max_gap, index)
+                    .find(|&next| next > last && chains.ways_from[next] > BigUint::from(0u32));
+                if let Some(sibling) = sibling {
+                    self.path.push(sibling);
+                    break;
+                }
+            }
+        }
+        chains.extend_lowest(&mut self.path);
+        Some(chains.to_joltages(&self.path))
+    }
+}
 
 fn main() {
     let contents = fs::read_to_string("input.txt").unwrap();
-    let sorted = get_sorted(&numbers_from_contents(&contents));
+    let sorted = get_sorted(&numbers_from_contents(&contents), 3);
     let diffs = count_jolt_differences(&sorted);
     println!("Multiple: {}", diffs[&1] * diffs[&3]);
 
-    let perms = count_jolt_permutations(&sorted);
+    let perms = count_jolt_permutations(&sorted, 3).unwrap();
     println!("Permutations: {}", perms); 
+
+    // Given a seed, print one arrangement picked at random, e.g. for fixtures;
+    // --list prints every arrangement instead, for small inputs.
+    match env::args().nth(1).as_deref() {
+        Some("--list") => {
+            let chains = Chains::new(&sorted, 3).unwrap();
+            for chain in chains.iter() {
+                println!("{:?}", chain);
+            }
+        }
+        Some(seed) => {
+            let chains = Chains::new(&sorted, 3).unwrap();
+            let mut rng = StdRng::seed_from_u64(seed.parse().unwrap());
+            println!("Sample: {:?}", chains.sample(&mut rng));
+        }
+        None => {}
+    }
 }
 
 #[test]
@@ -93,13 +248,13 @@ fn test_sample1()
     12
     4"#;
 
-    let sorted = get_sorted(&numbers_from_contents(SAMPLE));
+    let sorted = get_sorted(&numbers_from_contents(SAMPLE), 3);
     let diffs = count_jolt_differences(&sorted);
     assert_eq!(diffs[&1], 7);
     assert_eq!(diffs[&3], 5);
 
-    let perms = count_jolt_permutations(&sorted);
-    assert_eq!(perms, 8);
+    let perms = count_jolt_permutations(&sorted, 3).unwrap();
+    assert_eq!(perms, BigUint::from(8u32));
 }
 
 #[test]
@@ -137,11 +292,74 @@ fn test_sample2()
     10
     3"#;
 
-    let sorted = get_sorted(&numbers_from_contents(SAMPLE));
+    let sorted = get_sorted(&numbers_from_contents(SAMPLE), 3);
     let diffs = count_jolt_differences(&sorted);
     assert_eq!(diffs[&1], 22);
     assert_eq!(diffs[&3], 10);
 
-    let perms = count_jolt_permutations(&sorted);
-    assert_eq!(perms, 19208);
+    let perms = count_jolt_permutations(&sorted, 3).unwrap();
+    assert_eq!(perms, BigUint::from(19208u32));
+}
+
+#[test]
+fn test_gaps()
+{
+    // With a run of consecutive adapters and gaps of up to 2, the counts are
+    // Fibonacci numbers; the 102nd is well beyond u64.
+    let sorted = get_sorted(&(1..=100).collect::<Vec<u32>>(), 1);
+    let perms = count_jolt_permutations(&sorted, 2).unwrap();
+    assert_eq!(perms.to_string(), "927372692193078999176");
+
+    let sorted = get_sorted(&[1, 2, 7], 3);
+    assert_eq!(count_jolt_permutations(&sorted, 3),
+               Err(ChainError::Unreachable { from: 2, to: 7 }));
+
+    // A wider allowed gap bridges it, and 1 becomes optional.
+    assert_eq!(count_jolt_permutations(&sorted, 5).unwrap(), BigUint::from(2u32));
+
+    // A second zero-rated adapter can't follow the socket, but it can be
+    // left out, so only the device being unreachable is an error.
+    let sorted = get_sorted(&[0, 1], 3);
+    assert_eq!(count_jolt_permutations(&sorted, 3).unwrap(), BigUint::from(1u32));
+    assert_eq!(*Chains::new(&sorted, 3).unwrap().count(), BigUint::from(1u32));
+}
+
+#[test]
+fn test_chains()
+{
+    const SAMPLE: &str = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4";
+    let sorted = get_sorted(&numbers_from_contents(SAMPLE), 3);
+    let chains = Chains::new(&sorted, 3).unwrap();
+    assert_eq!(*chains.count(), count_jolt_permutations(&sorted, 3).unwrap());
+
+    let all: Vec<Vec<u32>> = chains.iter().collect();
+    assert_eq!(all.len(), 8);
+    assert_eq!(all[0], vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22]);
+    assert_eq!(all[7], vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22]);
+    for (k, chain) in all.iter().enumerate() {
+        assert_eq!(chains.nth(&BigUint::from(k)).as_ref(), Some(chain));
+    }
+    assert_eq!(chains.nth(&BigUint::from(8u32)), None);
+
+    let mut rng = StdRng::seed_from_u64(10);
+    for _ in 0..20 {
+        assert!(all.contains(&chains.sample(&mut rng)));
+    }
+
+    // Duplicate ratings can't follow one another, so the empty rows of
+    // ways_from have to be skipped rather than walked into.
+    let sorted = get_sorted(&[1, 1, 3], 2);
+    let chains = Chains::new(&sorted, 2).unwrap();
+    assert_eq!(chains.iter().collect::<Vec<_>>(),
+               vec![vec![0, 1, 3, 5], vec![0, 1, 3, 5]]);
+
+    // Enumeration agrees with the counter on small runs with various gaps.
+    for max_gap in 1..=4 {
+        for len in 1..=8 {
+            let sorted = get_sorted(&(1..=len).collect::<Vec<u32>>(), max_gap);
+            let chains = Chains::new(&sorted, max_gap).unwrap();
+            assert_eq!(BigUint::from(chains.iter().count()),
+                       count_jolt_permutations(&sorted, max_gap).unwrap());
+        }
+    }
 }
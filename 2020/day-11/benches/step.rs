@@ -0,0 +1,32 @@
+extern crate criterion;
+extern crate day_11;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use day_11::parallel::{generate_layout, SeatGrid};
+use day_11::{array_from_contents, next_step, step_until_stable, Rule};
+use std::fs;
+
+fn bench_step(c: &mut Criterion) {
+    // Random layouts can cycle forever, so only single steps are timed on them.
+    let layout = generate_layout(1000, 1000, 1);
+    let seats = array_from_contents(&layout, b'L');
+
+    c.bench_function("next_step 1000x1000", |b| b.iter(|| next_step(&seats, &seats, &Rule::Adjacent)));
+    c.bench_function("SeatGrid::step 1000x1000", |b| {
+        let mut grid = SeatGrid::from_arrays(&seats, &seats);
+        b.iter(|| grid.step(4))
+    });
+
+    // The puzzle input settles, so it can be run to the end.
+    let contents = fs::read_to_string("input.txt").unwrap();
+    let seats = array_from_contents(&contents, b'L');
+    c.bench_function("step_until_stable input", |b| b.iter(|| step_until_stable(&seats, &Rule::Adjacent).unwrap()));
+    c.bench_function("SeatGrid::step_until_stable input", |b| b.iter(|| {
+        let mut grid = SeatGrid::from_arrays(&seats, &seats);
+        grid.step_until_stable(4).unwrap();
+        grid.occupied()
+    }));
+}
+
+criterion_group!(benches, bench_step);
+criterion_main!(benches);
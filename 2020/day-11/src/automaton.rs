@@ -0,0 +1,260 @@
+use ndarray::{Array2, Zip};
+use std::collections::HashMap;
+
+pub type Grid = Array2<u8>;
+
+// The cells whose states feed into each cell's rule.
+pub enum Neighbourhood {
+    // The eight surrounding cells.
+    Moore,
+    // The four orthogonally adjacent cells.
+    VonNeumann,
+    // Any set of (dy, dx) offsets.
+    Offsets(Vec<(isize, isize)>),
+    // The six neighbours of a hex grid stored in axial coordinates, with the
+    // row as r and the column as q.
+    Hex,
+    // A fixed list of neighbour positions per cell, for neighbourhoods which
+    // depend on the layout (such as line of sight). Ignores the boundary.
+    Explicit(Array2<Vec<(usize, usize)>>)
+}
+
+impl Neighbourhood {
+    fn offsets(&self) -> Vec<(isize, isize)> {
+        match self {
+            Neighbourhood::Moore => vec![
+                (-1, -1), (-1, 0), (-1, 1),
+                (0, -1),           (0, 1),
+                (1, -1),  (1, 0),  (1, 1)],
+            Neighbourhood::VonNeumann => vec![(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighbourhood::Offsets(offsets) => offsets.clone(),
+            Neighbourhood::Hex => vec![(-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0)],
+            Neighbourhood::Explicit(_) => Vec::new()
+        }
+    }
+}
+
+// What lies beyond the edges of the grid.
+#[derive(Clone, Copy)]
+pub enum Boundary {
+    // Every outside cell is permanently in the given state.
+    Fixed(u8),
+    // The grid is a torus.
+    Wrapping
+}
+
+// A state outside 0..states, which has no slot in the neighbour counts.
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    Boundary(u8),
+    // A cell of the grid being stepped.
+    Cell { position: (usize, usize), state: u8 },
+    // What the rule returned for the cell at position.
+    Rule { position: (usize, usize), state: u8 }
+}
+
+// A grid which has started repeating: the states from step start onwards
+// recur every period steps. A stable grid has a period of one.
+#[derive(Debug, PartialEq)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+    pub grid: Grid
+}
+
+// Step state in place until it repeats, keeping a single checkpoint copy
+// rather than every generation (Brent's method). step returns whether the
+// state changed, so settling is caught without comparing. Leaves state at the
+// start of the cycle and returns that step and the period.
+pub fn find_period<T: Clone + PartialEq>(state: &mut T, mut step: impl FnMut(&mut T) -> bool) -> (usize, usize) {
+    let first = state.clone();
+    let mut checkpoint = state.clone();
+    let (mut steps, mut power, mut period) = (0, 1, 0);
+    loop {
+        if !step(state) {
+            return (steps, 1);
+        }
+        steps += 1;
+        period += 1;
+        if *state == checkpoint {
+            break;
+        }
+        if period == power {
+            checkpoint.clone_from(state);
+            power *= 2;
+            period = 0;
+        }
+    }
+
+    // Run one copy a period ahead of another from the beginning; they first
+    // meet where the cycle starts.
+    let mut ahead = checkpoint;
+    ahead.clone_from(&first);
+    for _ in 0..period {
+        step(&mut ahead);
+    }
+    state.clone_from(&first);
+    let mut start = 0;
+    while *state != ahead {
+        step(state);
+        step(&mut ahead);
+        start += 1;
+    }
+    (start, period)
+}
+
+// A cellular automaton over cells with states 0..states. The rule maps a
+// cell's state and the number of its neighbours in each state to the cell's
+// next state.
+pub struct Automaton<R> {
+    rule: R,
+    states: usize,
+    neighbourhood: Neighbourhood,
+    boundary: Boundary
+}
+
+impl<R: Fn(u8, &[u16]) -> u8> Automaton<R> {
+    pub fn new(states: usize, rule: R) -> Self {
+        Self { rule, states, neighbourhood: Neighbourhood::Moore, boundary: Boundary::Fixed(0) }
+    }
+
+    pub fn neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Result<Self, StateError> {
+        if let Boundary::Fixed(state) = boundary {
+            if state as usize >= self.states {
+                return Err(StateError::Boundary(state));
+            }
+        }
+        self.boundary = boundary;
+        Ok(self)
+    }
+
+    pub fn step(&self, grid: &Grid) -> Result<Grid, StateError> {
+        if let Some(((y, x), &state)) = grid.indexed_iter().find(|(_, &state)| state as usize >= self.states) {
+            return Err(StateError::Cell { position: (y, x), state });
+        }
+        let (yd, xd) = grid.dim();
+        let offsets = self.neighbourhood.offsets();
+        let mut counts = vec![0u16; self.states];
+        let mut next = Grid::zeros((yd, xd));
+        let mut bad_rule = None;
+        Zip::indexed(&mut next).and(grid).apply(|(y, x), next, &state| {
+            counts.iter_mut().for_each(|c| *c = 0);
+            if let Neighbourhood::Explicit(cells) = &self.neighbourhood {
+                for &position in &cells[[y, x]] {
+                    counts[grid[position] as usize] += 1;
+                }
+            }
+            for &(dy, dx) in &offsets {
+                let (ny, nx) = (y as isize + dy, x as isize + dx);
+                let neighbour = if ny >= 0 && nx >= 0 && (ny as usize) < yd && (nx as usize) < xd {
+                    grid[[ny as usize, nx as usize]]
+                } else {
+                    match self.boundary {
+                        Boundary::Fixed(state) => state,
+                        Boundary::Wrapping => grid[[
+                            ny.rem_euclid(yd as isize) as usize,
+                            nx.rem_euclid(xd as isize) as usize]]
+                    }
+                };
+                counts[neighbour as usize] += 1;
+            }
+            *next = (self.rule)(state, &counts);
+            if *next as usize >= self.states && bad_rule.is_none() {
+                bad_rule = Some(StateError::Rule { position: (y, x), state: *next });
+            }
+        });
+        match bad_rule {
+            Some(error) => Err(error),
+            None => Ok(next)
+        }
+    }
+
+    pub fn run(&self, grid: &Grid, steps: usize) -> Result<Grid, StateError> {
+        (0..steps).try_fold(grid.clone(), |grid, _| self.step(&grid))
+    }
+
+    // Step until a grid repeats. A finite grid always gets there eventually.
+    pub fn find_cycle(&self, grid: &Grid) -> Result<Cycle, StateError> {
+        let mut seen = HashMap::new();
+        let mut grid = grid.clone();
+        for step in 0.. {
+            if let Some(&start) = seen.get(&grid) {
+                return Ok(Cycle { start, period: step - start, grid });
+            }
+            let next = self.step(&grid)?;
+            seen.insert(grid, step);
+            grid = next;
+        }
+        unreachable!()
+    }
+
+    // The final grid if it settles, otherwise the cycle it falls into.
+    pub fn run_until_stable(&self, grid: &Grid) -> Result<Result<Grid, Cycle>, StateError> {
+        let cycle = self.find_cycle(grid)?;
+        if cycle.period == 1 {
+            Ok(Ok(cycle.grid))
+        } else {
+            Ok(Err(cycle))
+        }
+    }
+}
+
+#[test]
+fn test_blinker() {
+    use ndarray::array;
+
+    let life = Automaton::new(2, |state, counts| {
+        match (state, counts[1]) {
+            (1, 2) | (_, 3) => 1,
+            _ => 0
+        }
+    });
+    let blinker: Grid = array![
+        [0, 0, 0, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 1, 0, 0],
+        [0, 0, 0, 0, 0]];
+    assert_eq!(life.run(&blinker, 1).unwrap(), blinker.t());
+    let cycle = life.run_until_stable(&blinker).unwrap().unwrap_err();
+    assert_eq!((cycle.start, cycle.period), (0, 2));
+
+    // On a torus a glider comes back to where it started after travelling
+    // one square diagonally every four steps.
+    let life = life.boundary(Boundary::Wrapping).unwrap();
+    let mut glider = Grid::zeros((6, 6));
+    for &(y, x) in &[(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)] {
+        glider[[y, x]] = 1;
+    }
+    let cycle = life.find_cycle(&glider).unwrap();
+    assert_eq!((cycle.start, cycle.period), (0, 24));
+
+    // Growth on a hex grid stops when it reaches the fixed edge.
+    let grow = Automaton::new(2, |state, counts| if counts[1] > 0 { 1 } else { state })
+        .neighbourhood(Neighbourhood::Hex);
+    let mut seed = Grid::zeros((5, 5));
+    seed[[2, 2]] = 1;
+    let one_step = grow.step(&seed).unwrap();
+    assert_eq!(one_step.sum(), 7);
+    assert_eq!(grow.run_until_stable(&seed).unwrap().unwrap().sum(), 25);
+}
+
+#[test]
+fn test_bad_states() {
+    let count = |_, counts: &[u16]| counts[1] as u8;
+    assert_eq!(Automaton::new(2, count).boundary(Boundary::Fixed(2)).err(), Some(StateError::Boundary(2)));
+
+    let automaton = Automaton::new(2, count);
+    let mut grid = Grid::zeros((3, 3));
+    grid[[1, 2]] = 5;
+    assert_eq!(automaton.step(&grid), Err(StateError::Cell { position: (1, 2), state: 5 }));
+    // Once a cell has two live neighbours, the rule gives a state of 2.
+    grid[[1, 2]] = 1;
+    grid[[0, 0]] = 1;
+    assert_eq!(automaton.step(&grid), Err(StateError::Rule { position: (0, 1), state: 2 }));
+}
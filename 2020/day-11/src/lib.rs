@@ -0,0 +1,139 @@
+extern crate gif;
+extern crate ndarray;
+use ndarray::{Array2, s, concatenate, Axis, Zip};
+
+extern crate itertools;
+use itertools::concat;
+
+extern crate rand;
+extern crate rayon;
+
+pub mod automaton;
+pub mod parallel;
+pub mod render;
+
+use automaton::{find_period, Cycle};
+
+
+pub fn array_from_contents(contents: &str, ch: u8) -> Array2<u16> {
+    let width = contents.lines().next().unwrap().trim().len();
+    let rows = contents.lines()
+        .map(|line| line.trim().bytes()
+             .map(|byte| if byte == ch { 1 } else { 0 })
+             .collect::<Vec<_>>());
+    let data = concat(rows);
+    Array2::from_shape_vec((data.len() / width, width), data).unwrap()
+}
+
+pub fn surrounds_array(input: &Array2<u16>) -> Array2<u16> {
+    let (yd, xd) = input.dim();
+    let shift_up = |input: &Array2<u16>| concatenate!(Axis(0), Array2::zeros((1, xd)), input.slice(s![..-1, ..]));
+    let shift_down = |input: &Array2<u16>| concatenate!(Axis(0), input.slice(s![1.., ..]), Array2::zeros((1, xd)));
+    let shift_right = |input: &Array2<u16>| concatenate!(Axis(1), Array2::zeros((yd, 1)), input.slice(s![.., ..-1]));
+    let shift_left = |input: &Array2<u16>| concatenate!(Axis(1), input.slice(s![.., 1..]), Array2::zeros((yd, 1)));
+    let shifted_down = shift_down(input);
+    let shifted_up = shift_up(input);
+    &shifted_down + &shifted_up + shift_left(input) + shift_right(input) +
+        shift_left(&shifted_down) + shift_right(&shifted_down) +
+        shift_left(&shifted_up) + shift_right(&shifted_up)
+}
+
+const DIRECTIONS: [(isize, isize); 8] = [
+    (-1, -1), (-1, 0), (-1, 1),
+    (0, -1),           (0, 1),
+    (1, -1),  (1, 0),  (1, 1)];
+
+// For each seat, the positions of the first seat visible in each direction.
+// Floor never changes, so this only needs computing once per layout.
+pub fn visible_seats(seats: &Array2<u16>) -> Array2<Vec<(usize, usize)>> {
+    let (yd, xd) = seats.dim();
+    Array2::from_shape_fn((yd, xd), |(y, x)| {
+        if seats[[y, x]] == 0 {
+            return Vec::new();
+        }
+        DIRECTIONS.iter().filter_map(|&(dy, dx)| {
+            let (mut y, mut x) = (y as isize + dy, x as isize + dx);
+            while y >= 0 && x >= 0 && (y as usize) < yd && (x as usize) < xd {
+                if seats[[y as usize, x as usize]] == 1 {
+                    return Some((y as usize, x as usize));
+                }
+                y += dy;
+                x += dx;
+            }
+            None
+        }).collect()
+    })
+}
+
+// Which neighbours a seat counts, and how many occupied ones it will tolerate
+// before it empties.
+pub enum Rule {
+    Adjacent,
+    LineOfSight(Array2<Vec<(usize, usize)>>)
+}
+
+impl Rule {
+    pub fn line_of_sight(seats: &Array2<u16>) -> Self {
+        Rule::LineOfSight(visible_seats(seats))
+    }
+
+    pub fn tolerance(&self) -> u16 {
+        match self {
+            Rule::Adjacent => 4,
+            Rule::LineOfSight(_) => 5
+        }
+    }
+
+    pub fn surrounds(&self, occupied: &Array2<u16>) -> Array2<u16> {
+        match self {
+            Rule::Adjacent => surrounds_array(occupied),
+            Rule::LineOfSight(visible) => visible.map(|neighbours|
+                neighbours.iter().map(|&(y, x)| occupied[[y, x]]).sum())
+        }
+    }
+}
+
+pub fn next_step(seats: &Array2<u16>, occupied: &Array2<u16>, rule: &Rule) -> Array2<u16> {
+    let surrounds = rule.surrounds(occupied);
+    let tolerance = rule.tolerance();
+    let mut new_occupied = occupied.clone();
+    Zip::from(&mut new_occupied).and(seats).and(&surrounds)
+        .apply(|occupied, &seat, &surrounds| {
+            if seat == 1 && *occupied == 1 && surrounds >= tolerance {
+                *occupied = 0
+            } else if seat == 1 && *occupied == 0 && surrounds == 0 {
+                *occupied = 1
+            }
+        });
+    new_occupied
+}
+
+// The occupied seats once they settle, or the cycle they fall into instead
+// (with floor, empty and occupied as 0, 1 and 2, as for SeatGrid).
+pub fn step_until_stable(seats: &Array2<u16>, rule: &Rule) -> Result<Array2<u16>, Cycle> {
+    // Assume all seats begin as full.
+    let mut occupied = seats.clone();
+    let (start, period) = find_period(&mut occupied, |occupied| {
+        let new_occupied = next_step(seats, occupied, rule);
+        let changed = *occupied != new_occupied;
+        *occupied = new_occupied;
+        changed
+    });
+    if period == 1 {
+        Ok(occupied)
+    } else {
+        Err(Cycle { start, period, grid: (seats + &occupied).mapv(|v| v as u8) })
+    }
+}
+
+// Every generation from all seats empty through to the stable one.
+pub fn generations(seats: &Array2<u16>, rule: &Rule) -> Vec<Array2<u16>> {
+    let mut generations = vec![Array2::zeros(seats.dim())];
+    loop {
+        let next = next_step(seats, generations.last().unwrap(), rule);
+        if next == *generations.last().unwrap() {
+            return generations;
+        }
+        generations.push(next);
+    }
+}
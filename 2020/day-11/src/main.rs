@@ -1,69 +1,56 @@
-extern crate ndarray;
-use ndarray::{Array2, s, concatenate, Axis, Zip};
-
-extern crate itertools;
-use itertools::concat;
+extern crate day_11;
 
+use day_11::*;
+use std::env;
 use std::fs;
+use std::process;
+use std::time::Duration;
 
+const USAGE: &str = "usage: day-11 [--animate MILLISECONDS] [--gif FILENAME]";
 
-fn array_from_contents(contents: &str, ch: u8) -> Array2<u16> {
-    let width = contents.lines().next().unwrap().trim().as_bytes().len();
-    let rows = contents.lines()
-        .map(|line| line.trim().bytes()
-             .map(|byte| if byte == ch { 1 } else { 0 })
-             .collect::<Vec<_>>());
-    let data = concat(rows);
-    Array2::from_shape_vec((data.len() / width, width), data).unwrap()
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
 }
 
-fn surrounds_array(input: &Array2<u16>) -> Array2<u16> {
-    let (yd, xd) = input.dim();
-    let shift_up = |input: &Array2<u16>| concatenate!(Axis(0), Array2::zeros((1, xd)), input.slice(s![..-1, ..]));
-    let shift_down = |input: &Array2<u16>| concatenate!(Axis(0), input.slice(s![1.., ..]), Array2::zeros((1, xd)));
-    let shift_right = |input: &Array2<u16>| concatenate!(Axis(1), Array2::zeros((yd, 1)), input.slice(s![.., ..-1]));
-    let shift_left = |input: &Array2<u16>| concatenate!(Axis(1), input.slice(s![.., 1..]), Array2::zeros((yd, 1)));
-    let shifted_down = shift_down(input);
-    let shifted_up = shift_up(input);
-    &shifted_down + &shifted_up + shift_left(input) + shift_right(input) +
-        shift_left(&shifted_down) + shift_right(&shifted_down) +
-        shift_left(&shifted_up) + shift_right(&shifted_up)
-}
+fn main() {
+    // Watch it converge with --animate MILLISECONDS, or save it with --gif FILENAME.
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| match args.get(i + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(&format!("{} needs a value", name))
+    });
+    let animate = option("--animate");
+    let gif = option("--gif");
+    let delay = Duration::from_millis(animate.as_ref().map_or(100, |ms| {
+        ms.parse().unwrap_or_else(|_| usage_error(&format!("bad --animate value {:?}", ms)))
+    }));
 
-fn next_step(seats: &Array2<u16>, occupied: &Array2<u16>) -> Array2<u16> {
-    let surrounds = surrounds_array(&occupied);
-    let mut new_occupied = occupied.clone();
-    Zip::from(&mut new_occupied).and(seats).and(&surrounds)
-        .apply(|occupied, &seat, &surrounds| {
-            if seat == 1 && *occupied == 1 && surrounds >= 4 {
-                *occupied = 0
-            } else if seat == 1 && *occupied == 0 && surrounds == 0 {
-                *occupied = 1
-            }
-        });
-    new_occupied
-}
+    let contents = fs::read_to_string("input.txt").unwrap();
+    let seats = array_from_contents(&contents, b'L');
+    let report = |label: &str, rule: &Rule| match step_until_stable(&seats, rule) {
+        Ok(occupied) => println!("Occupied seats{}: {}", label, occupied.sum()),
+        Err(cycle) => println!("Occupied seats{}: none stable, cycles every {} steps from step {}",
+                               label, cycle.period, cycle.start)
+    };
+    report("", &Rule::Adjacent);
+    report(", line of sight", &Rule::line_of_sight(&seats));
 
-fn step_until_stable(seats: &Array2<u16>) -> Array2<u16> {
-    // Assume all seats begin as full.
-    let mut occupied = seats.clone();
-    loop {
-        let new_occupied = next_step(&seats, &occupied);
-        //println!("{:?}", new_occupied);
-        if occupied == new_occupied {
-            return occupied;
+    if animate.is_none() && gif.is_none() {
+        return;
+    }
+    let frames = generations(&seats, &Rule::line_of_sight(&seats));
+    if animate.is_some() {
+        render::animate_terminal(&seats, &frames, delay).unwrap();
+    }
+    if let Some(filename) = gif {
+        if let Err(error) = render::write_gif(&filename, &seats, &frames, delay, 4) {
+            eprintln!("can't write {}: {}", filename, error);
+            process::exit(1);
         }
-        occupied = new_occupied;
     }
 }
 
-fn main() {
-    let contents = fs::read_to_string("input.txt").unwrap();
-    let seats = array_from_contents(&contents, b'L');
-    let occupied = step_until_stable(&seats);
-    println!("Occupied seats: {}", occupied.sum());
-}
-
 
 #[test]
 fn test_sample() {
@@ -92,7 +79,7 @@ fn test_sample() {
     #LLLLLLLL#
     #.LLLLLL.L
     #.#LLLL.##";
-    occupied = next_step(&seats, &occupied);
+    occupied = next_step(&seats, &occupied, &Rule::Adjacent);
     assert_eq!(occupied, array_from_contents(STEP2, b'#'));
 
     const STEP3: &str = "\
@@ -106,7 +93,7 @@ fn test_sample() {
     #L######L#
     #.LL###L.L
     #.#L###.##";
-    occupied = next_step(&seats, &occupied);
+    occupied = next_step(&seats, &occupied, &Rule::Adjacent);
     assert_eq!(occupied, array_from_contents(STEP3, b'#'));
 
     const STEP4: &str = "\
@@ -120,7 +107,7 @@ fn test_sample() {
     #L#LLLL#L#
     #.LLLLLL.L
     #.#L#L#.##";
-    occupied = next_step(&seats, &occupied);
+    occupied = next_step(&seats, &occupied, &Rule::Adjacent);
     assert_eq!(occupied, array_from_contents(STEP4, b'#'));
 
     const STEP5: &str = "\
@@ -134,13 +121,16 @@ fn test_sample() {
     #L#L##L#L#
     #.LLLLLL.L
     #.#L#L#.##";
-    occupied = next_step(&seats, &occupied);
+    occupied = next_step(&seats, &occupied, &Rule::Adjacent);
     assert_eq!(occupied, array_from_contents(STEP5, b'#'));
 
     // Re-run it from the beginning to let the function
     // determine the final state.
-    occupied = step_until_stable(&seats);
+    occupied = step_until_stable(&seats, &Rule::Adjacent).unwrap();
     assert_eq!(occupied.sum(), 37);
+
+    occupied = step_until_stable(&seats, &Rule::line_of_sight(&seats)).unwrap();
+    assert_eq!(occupied.sum(), 26);
 }
 
 #[test]
@@ -158,3 +148,170 @@ fn test_surrounds() {
         [5,7,8,4],
         [3,4,4,2]]);
 }
+
+#[test]
+fn test_line_of_sight() {
+    const SEATS: &str = "\
+    .............
+    .L.L.#.#.#.#.
+    .............";
+    let seats = array_from_contents(SEATS, b'L') + array_from_contents(SEATS, b'#');
+    let occupied = array_from_contents(SEATS, b'#');
+    let rule = Rule::line_of_sight(&seats);
+
+    // The leftmost seat can only see the empty seat next to it.
+    assert_eq!(rule.surrounds(&occupied)[[1, 1]], 0);
+    assert_eq!(rule.surrounds(&occupied)[[1, 3]], 1);
+
+    const SAMPLE: &str = "\
+    L.LL.LL.LL
+    LLLLLLL.LL
+    L.L.L..L..
+    LLLL.LL.LL
+    L.LL.LL.LL
+    L.LLLLL.LL
+    ..L.L.....
+    LLLLLLLLLL
+    L.LLLLLL.L
+    L.LLLLL.LL";
+    const STEP3: &str = "\
+    #.LL.LL.L#
+    #LLLLLL.LL
+    L.L.L..L..
+    LLLL.LL.LL
+    L.LL.LL.LL
+    L.LLLLL.LL
+    ..L.L.....
+    LLLLLLLLL#
+    #.LLLLLL.L
+    #.LLLLL.L#";
+    let seats = array_from_contents(SAMPLE, b'L');
+    let rule = Rule::line_of_sight(&seats);
+    // As with step_until_stable, start from every seat being full.
+    let occupied = next_step(&seats, &seats, &rule);
+    assert_eq!(occupied, array_from_contents(STEP3, b'#'));
+}
+
+#[test]
+fn test_automaton() {
+    use automaton::{Automaton, Grid, Neighbourhood};
+    use ndarray::Array2;
+
+    // The seat rules on the general engine, with floor, empty and occupied
+    // as states 0, 1 and 2.
+    fn seat_automaton(tolerance: u16, neighbourhood: Neighbourhood) -> Automaton<impl Fn(u8, &[u16]) -> u8> {
+        Automaton::new(3, move |state, counts| {
+            match state {
+                1 if counts[2] == 0 => 2,
+                2 if counts[2] >= tolerance => 1,
+                _ => state
+            }
+        }).neighbourhood(neighbourhood)
+    }
+
+    fn seat_grid(seats: &Array2<u16>, occupied: &Array2<u16>) -> Grid {
+        (seats + occupied).mapv(|v| v as u8)
+    }
+
+    const SEATS: &str = "\
+    L.LL.LL.LL
+    LLLLLLL.LL
+    L.L.L..L..
+    LLLL.LL.LL
+    L.LL.LL.LL
+    L.LLLLL.LL
+    ..L.L.....
+    LLLLLLLLLL
+    L.LLLLLL.L
+    L.LLLLL.LL";
+    let seats = array_from_contents(SEATS, b'L');
+    let grid = seat_grid(&seats, &Array2::zeros(seats.dim()));
+
+    let adjacent = seat_automaton(4, Neighbourhood::Moore);
+    let stable = adjacent.run_until_stable(&grid).unwrap().unwrap();
+    assert_eq!(stable.iter().filter(|&&s| s == 2).count(), 37);
+    assert_eq!(stable, seat_grid(&seats, &step_until_stable(&seats, &Rule::Adjacent).unwrap()));
+
+    let line_of_sight = seat_automaton(5, Neighbourhood::Explicit(visible_seats(&seats)));
+    let stable = line_of_sight.run_until_stable(&grid).unwrap().unwrap();
+    assert_eq!(stable.iter().filter(|&&s| s == 2).count(), 26);
+}
+
+#[test]
+fn test_render() {
+    const SEATS: &str = "\
+    L.LL.LL.LL
+    LLLLLLL.LL
+    L.L.L..L..
+    LLLL.LL.LL
+    L.LL.LL.LL
+    L.LLLLL.LL
+    ..L.L.....
+    LLLLLLLLLL
+    L.LLLLLL.L
+    L.LLLLL.LL";
+    const STABLE: &str = "\
+#.#L.L#.##
+#LLL#LL.L#
+L.#.L..#..
+#L##.##.L#
+#.#L.LL.LL
+#.#L#L#.##
+..L.L.....
+#L#L##L#L#
+#.LLLLLL.L
+#.#L#L#.##
+";
+    let seats = array_from_contents(SEATS, b'L');
+    let frames = generations(&seats, &Rule::Adjacent);
+    assert_eq!(frames.len(), 6);
+    assert_eq!(render::render_frame(&seats, &frames[0]), SEATS.replace(' ', "") + "\n");
+    assert_eq!(render::render_frame(&seats, frames.last().unwrap()), STABLE);
+
+    let filename = env::temp_dir().join("day-11-test.gif");
+    render::write_gif(filename.to_str().unwrap(), &seats, &frames, Duration::from_millis(50), 2).unwrap();
+    assert!(fs::read(&filename).unwrap().starts_with(b"GIF89a"));
+
+    // Too big for the 16-bit sizes in a GIF header.
+    let error = render::write_gif(filename.to_str().unwrap(), &seats, &frames,
+                                  Duration::from_millis(50), 10000).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_parallel() {
+    use day_11::parallel::{generate_layout, SeatGrid};
+
+    for (height, width, seed) in &[(10, 10, 1), (37, 53, 2), (1, 40, 3), (64, 1, 4)] {
+        let layout = generate_layout(*height, *width, *seed);
+        let seats = array_from_contents(&layout, b'L');
+        let mut occupied = seats.clone();
+        let mut grid = SeatGrid::from_contents(&layout.replace('L', "#"));
+        // Random layouts can settle into a blinking cycle, so give up comparing
+        // after a while.
+        for _ in 0..200 {
+            let next = next_step(&seats, &occupied, &Rule::Adjacent);
+            let changed = grid.step(4);
+            assert_eq!(grid.to_occupied(), next);
+            assert_eq!(changed, next != occupied);
+            if !changed {
+                break;
+            }
+            occupied = next;
+        }
+    }
+
+    let contents = fs::read_to_string("input.txt").unwrap();
+    let seats = array_from_contents(&contents, b'L');
+    let mut grid = SeatGrid::from_arrays(&seats, &seats);
+    grid.step_until_stable(4).unwrap();
+    assert_eq!(grid.occupied() as u16, step_until_stable(&seats, &Rule::Adjacent).unwrap().sum());
+
+    // This one never settles, and both versions find the same blinking cycle.
+    let layout = generate_layout(20, 20, 77);
+    let seats = array_from_contents(&layout, b'L');
+    let cycle = step_until_stable(&seats, &Rule::Adjacent).unwrap_err();
+    assert_eq!((cycle.start, cycle.period), (15, 2));
+    let mut grid = SeatGrid::from_arrays(&seats, &seats);
+    assert_eq!(grid.step_until_stable(4), Err(cycle));
+}
@@ -0,0 +1,127 @@
+use crate::automaton::{find_period, Cycle, Grid};
+use ndarray::Array2;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+
+const FLOOR: u8 = 0;
+const EMPTY: u8 = 1;
+const OCCUPIED: u8 = 2;
+
+// A deterministic layout of roughly 85% seats, for tests and benchmarks.
+pub fn generate_layout(height: usize, width: usize, seed: u64) -> String {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut layout = String::with_capacity((width + 1) * height);
+    for _ in 0..height {
+        for _ in 0..width {
+            layout.push(if rng.gen_bool(0.85) { 'L' } else { '.' });
+        }
+        layout.push('\n');
+    }
+    layout
+}
+
+// The adjacent-seat rule on a flat byte grid, for layouts too large for the
+// ndarray version. Steps write into a second buffer, a row per task, and then
+// swap the two, so nothing is allocated after construction.
+#[derive(Clone)]
+pub struct SeatGrid {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+    next: Vec<u8>
+}
+
+impl SeatGrid {
+    pub fn from_contents(contents: &str) -> Self {
+        let rows: Vec<&[u8]> = contents.lines().map(|line| line.trim().as_bytes()).collect();
+        let width = rows[0].len();
+        let cells: Vec<u8> = rows.iter()
+            .flat_map(|row| row.iter().map(|&byte| match byte {
+                b'L' => EMPTY,
+                b'#' => OCCUPIED,
+                _ => FLOOR
+            }))
+            .collect();
+        Self { width, height: rows.len(), next: cells.clone(), cells }
+    }
+
+    pub fn from_arrays(seats: &Array2<u16>, occupied: &Array2<u16>) -> Self {
+        let (height, width) = seats.dim();
+        let cells: Vec<u8> = seats.iter().zip(occupied.iter())
+            .map(|(&seat, &occupied)| (seat + occupied) as u8)
+            .collect();
+        Self { width, height, next: cells.clone(), cells }
+    }
+
+    // The occupied seats as a 0/1 array, for comparing against next_step.
+    pub fn to_occupied(&self) -> Array2<u16> {
+        let data = self.cells.iter().map(|&c| (c == OCCUPIED) as u16).collect();
+        Array2::from_shape_vec((self.height, self.width), data).unwrap()
+    }
+
+    pub fn to_grid(&self) -> Grid {
+        Array2::from_shape_vec((self.height, self.width), self.cells.clone()).unwrap()
+    }
+
+    pub fn occupied(&self) -> usize {
+        self.cells.par_iter().filter(|&&c| c == OCCUPIED).count()
+    }
+
+    // Apply one generation, returning whether any seat changed.
+    pub fn step(&mut self, tolerance: u8) -> bool {
+        let (width, height) = (self.width, self.height);
+        let cells = &self.cells;
+        let changed = self.next.par_chunks_mut(width).enumerate()
+            .map(|(y, row)| {
+                let mut changed = false;
+                let above = if y > 0 { y - 1 } else { y };
+                let below = if y + 1 < height { y + 1 } else { y };
+                for (x, out) in row.iter_mut().enumerate() {
+                    let cell = cells[y * width + x];
+                    if cell == FLOOR {
+                        *out = FLOOR;
+                        continue;
+                    }
+                    let left = if x > 0 { x - 1 } else { x };
+                    let right = if x + 1 < width { x + 1 } else { x };
+                    let mut surrounds = 0;
+                    for ny in above..=below {
+                        for nx in left..=right {
+                            if (ny != y || nx != x) && cells[ny * width + nx] == OCCUPIED {
+                                surrounds += 1;
+                            }
+                        }
+                    }
+                    *out = match cell {
+                        EMPTY if surrounds == 0 => OCCUPIED,
+                        OCCUPIED if surrounds >= tolerance => EMPTY,
+                        _ => cell
+                    };
+                    changed |= *out != cell;
+                }
+                changed
+            })
+            .reduce(|| false, |a, b| a || b);
+        std::mem::swap(&mut self.cells, &mut self.next);
+        changed
+    }
+
+    // As step_until_stable, starting from whatever the grid currently holds,
+    // giving the number of steps it took to settle.
+    pub fn step_until_stable(&mut self, tolerance: u8) -> Result<usize, Cycle> {
+        let (start, period) = find_period(self, |grid| grid.step(tolerance));
+        if period == 1 {
+            Ok(start)
+        } else {
+            Err(Cycle { start, period, grid: self.to_grid() })
+        }
+    }
+}
+
+// Only the cells count; next is scratch space.
+impl PartialEq for SeatGrid {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.cells == other.cells
+    }
+}
@@ -0,0 +1,78 @@
+use gif::{Encoder, Frame, Repeat};
+use ndarray::Array2;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, Write};
+use std::thread;
+use std::time::Duration;
+
+// Floor, empty seat and occupied seat, as in the puzzle text.
+fn cell_state(seat: u16, occupied: u16) -> usize {
+    (seat + occupied) as usize
+}
+
+pub fn render_frame(seats: &Array2<u16>, occupied: &Array2<u16>) -> String {
+    const CHARS: [char; 3] = ['.', 'L', '#'];
+    let mut out = String::new();
+    for (seat_row, occupied_row) in seats.outer_iter().zip(occupied.outer_iter()) {
+        for (&seat, &occupied) in seat_row.iter().zip(occupied_row.iter()) {
+            out.push(CHARS[cell_state(seat, occupied)]);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Redraw each generation in place, pausing for delay between them.
+pub fn animate_terminal(seats: &Array2<u16>, generations: &[Array2<u16>], delay: Duration) -> io::Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for (index, occupied) in generations.iter().enumerate() {
+        write!(out, "\x1b[2J\x1b[H{}", render_frame(seats, occupied))?;
+        writeln!(out, "Generation {}: {} occupied", index, occupied.sum())?;
+        out.flush()?;
+        thread::sleep(delay);
+    }
+    Ok(())
+}
+
+// GIF sizes and delays are 16-bit, so anything bigger is refused rather
+// than wrapped.
+fn gif_u16(value: usize, what: &str) -> io::Result<u16> {
+    u16::try_from(value).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput,
+        format!("GIF {} of {} is too large", what, value)))
+}
+
+// Write the generations out as a looping GIF, with each cell drawn as a
+// square of scale pixels.
+pub fn write_gif(filename: &str, seats: &Array2<u16>, generations: &[Array2<u16>],
+                 delay: Duration, scale: usize) -> io::Result<()> {
+    const PALETTE: [u8; 9] = [
+        0x20, 0x20, 0x20,
+        0x40, 0xa0, 0x40,
+        0xd0, 0x30, 0x30];
+    let (yd, xd) = seats.dim();
+    let (height, width) = (yd * scale, xd * scale);
+    let (gif_width, gif_height) = (gif_u16(width, "width")?, gif_u16(height, "height")?);
+    // GIF delays are in hundredths of a second.
+    let gif_delay = gif_u16((delay.as_millis() / 10) as usize, "delay")?;
+    let mut encoder = Encoder::new(File::create(filename)?, gif_width, gif_height, &PALETTE)
+        .map_err(io::Error::other)?;
+    encoder.set_repeat(Repeat::Infinite)
+        .map_err(io::Error::other)?;
+
+    for occupied in generations {
+        let mut pixels = vec![0u8; width * height];
+        for ((y, x), &seat) in seats.indexed_iter() {
+            let state = cell_state(seat, occupied[[y, x]]) as u8;
+            for row in (y * scale)..((y + 1) * scale) {
+                pixels[(row * width + x * scale)..(row * width + (x + 1) * scale)].fill(state);
+            }
+        }
+        let mut frame = Frame::from_indexed_pixels(gif_width, gif_height, &pixels, None);
+        frame.delay = gif_delay;
+        encoder.write_frame(&frame)
+            .map_err(io::Error::other)?;
+    }
+    Ok(())
+}
@@ -1,89 +1,281 @@
 extern crate euclid;
 
-use euclid::Vector2D;
+use euclid::{Angle, Rotation2D, Vector2D};
+use std::env;
 use std::fs;
+use std::io::{self, Write};
+use std::ops::AddAssign;
+use std::process;
 use std::vec::Vec;
 
 #[derive(Debug)]
 struct Instr {
     action: char,
-    value: i32
+    // Always a whole number in Mode::Grid.
+    value: f64
 }
 
 type Vec2 = Vector2D<i32, i32>;
+type FVec2 = Vector2D<f64, f64>;
 
-fn program_from_contents(contents: &str) -> Vec<Instr> {
-    contents.lines().map(|line| {
-        let mut ch = line.trim().chars();
-        Instr {
-            action: ch.next().unwrap(),
-            value: ch.as_str().parse().unwrap()
-        }
-    }).collect()
+// Whether turns are limited to the right angles of the puzzle, which keep
+// positions on the integer grid, or may be anything with positions in f64.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode { Grid, Float }
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    UnknownAction { line: usize, action: char },
+    BadValue { line: usize, value: String },
+    BadAngle { line: usize, degrees: i32 }
+}
+
+fn program_from_contents(contents: &str, mode: Mode) -> Result<Vec<Instr>, ParseError> {
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let line_number = index + 1;
+            let mut ch = line.trim().chars();
+            let action = ch.next().unwrap();
+            if !"NSEWLRF".contains(action) {
+                return Err(ParseError::UnknownAction { line: line_number, action });
+            }
+            let text = ch.as_str();
+            let bad_value = || ParseError::BadValue { line: line_number, value: text.to_string() };
+            let value = match mode {
+                Mode::Grid => {
+                    let value: i32 = text.parse().map_err(|_| bad_value())?;
+                    if (action == 'L' || action == 'R') && value % 90 != 0 {
+                        return Err(ParseError::BadAngle { line: line_number, degrees: value });
+                    }
+                    value as f64
+                }
+                Mode::Float => text.parse::<f64>().ok().filter(|v| v.is_finite()).ok_or_else(bad_value)?
+            };
+            Ok(Instr { action, value })
+        })
+        .collect()
+}
+
+// The vector operations navigation needs, so that the same rules can run on
+// the integer grid or in floating point. Turns are counterclockwise as seen
+// on a map, with north as negative y.
+trait Heading: Copy + AddAssign {
+    fn new(x: i32, y: i32) -> Self;
+    fn rotated(self, degrees: f64) -> Self;
+    fn scaled(self, value: f64) -> Self;
+    fn xy(&self) -> (f64, f64);
+}
+
+impl Heading for Vec2 {
+    fn new(x: i32, y: i32) -> Self {
+        Vec2::new(x, y)
+    }
+    // Grid programs only hold whole numbers, so these convert exactly.
+    fn rotated(self, degrees: f64) -> Self {
+        rotate_vector(&self, degrees as i32)
+    }
+    fn scaled(self, value: f64) -> Self {
+        self * value as i32
+    }
+    fn xy(&self) -> (f64, f64) {
+        (self.x as f64, self.y as f64)
+    }
+}
+
+impl Heading for FVec2 {
+    fn new(x: i32, y: i32) -> Self {
+        FVec2::new(x as f64, y as f64)
+    }
+    fn rotated(self, degrees: f64) -> Self {
+        // With y pointing south, a counterclockwise turn is a negative angle.
+        Rotation2D::new(Angle::degrees(-degrees)).transform_vector(self)
+    }
+    fn scaled(self, value: f64) -> Self {
+        self * value
+    }
+    fn xy(&self) -> (f64, f64) {
+        (self.x, self.y)
+    }
+}
+
+// Where the ship is, and its heading (for the first rules) or waypoint
+// (for the second), both relative to the start.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pose<V> {
+    ship: V,
+    waypoint: V
+}
+
+type Trajectory<V> = Vec<Pose<V>>;
+
+fn manhattan<V: Heading>(v: &V) -> f64 {
+    let (x, y) = v.xy();
+    x.abs() + y.abs()
 }
 
 fn rotate_vector(input: &Vec2, degrees: i32) -> Vec2 {
-    let mut rotated = input.clone();
-    for _rotations in 0..(degrees / 90) {
+    let mut rotated = *input;
+    for _rotations in 0..(degrees.rem_euclid(360) / 90) {
         rotated = Vec2::new(rotated.y, -rotated.x);
     }
     rotated
 }
 
-fn run_program1(program: &[Instr]) -> Vec2 {
-    let mut ship_pose = Vec2::new(0, 0);
-    let mut facing = Vec2::new(1, 0);
+fn compass<V: Heading>(action: char) -> V {
+    match action {
+        'E' => V::new(1, 0),
+        'W' => V::new(-1, 0),
+        'N' => V::new(0, -1),
+        'S' => V::new(0, 1),
+        _ => unreachable!()
+    }
+}
+
+// The pose before the program and after each instruction.
+fn trajectory1<V: Heading>(program: &[Instr]) -> Trajectory<V> {
+    let mut ship_pose = V::new(0, 0);
+    let mut facing = V::new(1, 0);
+    let mut trajectory = vec![Pose { ship: ship_pose, waypoint: facing }];
 
     for instr in program.iter() {
         match instr.action {
-            'L' => facing = rotate_vector(&facing, instr.value),
-            'R' => facing = rotate_vector(&facing, 360 - instr.value),
-            _ => {
-                ship_pose += match instr.action {
-                    'F' => facing,
-                    'E' => Vec2::new(1, 0),
-                    'W' => Vec2::new(-1, 0),
-                    'N' => Vec2::new(0, -1),
-                    'S' => Vec2::new(0, 1),
-                    _ => unreachable!()
-                } * instr.value;
-            }
+            'L' => facing = facing.rotated(instr.value),
+            'R' => facing = facing.rotated(-instr.value),
+            'F' => ship_pose += facing.scaled(instr.value),
+            _ => ship_pose += compass::<V>(instr.action).scaled(instr.value)
         }
+        trajectory.push(Pose { ship: ship_pose, waypoint: facing });
     }
-    ship_pose
+    trajectory
 }
 
-fn run_program2(program: &[Instr]) -> Vec2 {
-    let mut ship_pose = Vec2::new(0, 0);
-    let mut waypoint = Vec2::new(10, -1);
+fn trajectory2<V: Heading>(program: &[Instr]) -> Trajectory<V> {
+    let mut ship_pose = V::new(0, 0);
+    let mut waypoint = V::new(10, -1);
+    let mut trajectory = vec![Pose { ship: ship_pose, waypoint }];
 
     for instr in program.iter() {
         match instr.action {
-            'L' => waypoint = rotate_vector(&waypoint, instr.value),
-            'R' => waypoint = rotate_vector(&waypoint, 360 - instr.value),
-            'F' => ship_pose += waypoint * instr.value,
-            _ => {
-                waypoint += match instr.action {
-                    'E' => Vec2::new(1, 0),
-                    'W' => Vec2::new(-1, 0),
-                    'N' => Vec2::new(0, -1),
-                    'S' => Vec2::new(0, 1),
-                    _ => unreachable!()
-                } * instr.value;
-            }
+            'L' => waypoint = waypoint.rotated(instr.value),
+            'R' => waypoint = waypoint.rotated(-instr.value),
+            'F' => ship_pose += waypoint.scaled(instr.value),
+            _ => waypoint += compass::<V>(instr.action).scaled(instr.value)
         }
+        trajectory.push(Pose { ship: ship_pose, waypoint });
     }
-    ship_pose
+    trajectory
+}
+
+fn run_program1<V: Heading>(program: &[Instr]) -> V {
+    trajectory1(program).last().unwrap().ship
+}
+
+fn run_program2<V: Heading>(program: &[Instr]) -> V {
+    trajectory2(program).last().unwrap().ship
+}
+
+fn write_csv<W: Write, V: Heading>(out: &mut W, program: &[Instr], trajectory: &[Pose<V>]) -> io::Result<()> {
+    writeln!(out, "step,action,value,ship_x,ship_y,waypoint_x,waypoint_y")?;
+    for (step, pose) in trajectory.iter().enumerate() {
+        let (action, value) = match step {
+            0 => (String::new(), String::new()),
+            _ => (program[step - 1].action.to_string(), program[step - 1].value.to_string())
+        };
+        let (ship, waypoint) = (pose.ship.xy(), pose.waypoint.xy());
+        writeln!(out, "{},{},{},{},{},{},{}", step, action, value,
+                 ship.0, ship.1, waypoint.0, waypoint.1)?;
+    }
+    Ok(())
+}
+
+// The ship's course as a polyline, with north up as in the puzzle's
+// coordinates (where north is negative y). The waypoint at each step is
+// drawn as a faint line from the ship.
+fn write_svg<W: Write, V: Heading>(out: &mut W, trajectory: &[Pose<V>]) -> io::Result<()> {
+    let ships: Vec<(f64, f64)> = trajectory.iter().map(|p| p.ship.xy()).collect();
+    let min_x = ships.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let max_x = ships.iter().map(|p| p.0).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = ships.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let max_y = ships.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let margin = ((max_x - min_x).max(max_y - min_y) / 20.0).floor() + 1.0;
+    let stroke = margin / 10.0;
+
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+             min_x - margin, min_y - margin, max_x - min_x + 2.0 * margin, max_y - min_y + 2.0 * margin)?;
+    for pose in trajectory {
+        let (x, y) = pose.ship.xy();
+        let (dx, dy) = pose.waypoint.xy();
+        writeln!(out, r#"  <line x1="{}" y1="{}" x2="{}" y2="{}" stroke="lightgrey" stroke-width="{}"/>"#,
+                 x, y, x + dx, y + dy, stroke)?;
+    }
+    let points: Vec<String> = ships.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+    writeln!(out, r#"  <polyline points="{}" fill="none" stroke="black" stroke-width="{}"/>"#,
+             points.join(" "), stroke)?;
+    let (start, end) = (ships[0], ships[ships.len() - 1]);
+    writeln!(out, r#"  <circle cx="{}" cy="{}" r="{}" fill="green"/>"#, start.0, start.1, stroke * 3.0)?;
+    writeln!(out, r#"  <circle cx="{}" cy="{}" r="{}" fill="red"/>"#, end.0, end.1, stroke * 3.0)?;
+    writeln!(out, "</svg>")
+}
+
+// What to export, and the course by which rules to take it from.
+struct Export {
+    rules: u8,
+    csv: Option<String>,
+    svg: Option<String>
+}
+
+// Print both distances, and export a course if asked.
+fn report<V: Heading>(program: &[Instr], export: Export) {
+    println!("Distance by first rules: {}", manhattan(&run_program1::<V>(program)));
+    println!("Distance by second rules: {}", manhattan(&run_program2::<V>(program)));
+    if export.csv.is_none() && export.svg.is_none() {
+        return;
+    }
+
+    let trajectory: Trajectory<V> = match export.rules {
+        1 => trajectory1(program),
+        _ => trajectory2(program)
+    };
+    if let Some(filename) = export.csv {
+        write_csv(&mut fs::File::create(filename).unwrap(), program, &trajectory).unwrap();
+    }
+    if let Some(filename) = export.svg {
+        write_svg(&mut fs::File::create(filename).unwrap(), &trajectory).unwrap();
+    }
+}
+
+const USAGE: &str = "usage: day-12 [--float] [--rules 1|2] [--csv FILENAME] [--svg FILENAME]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
 }
 
 fn main() {
-    let contents = fs::read_to_string("input.txt").unwrap();
-    let program = program_from_contents(&contents);
-    let result1 = run_program1(&program);
-    println!("Distance by first rules: {}", (result1.x + result1.y).abs());
+    // Pass --float to allow turns by any angle, and --csv FILENAME or
+    // --svg FILENAME to export the course, by the second rules unless
+    // --rules 1 is given.
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| match args.get(i + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(&format!("{} needs a value", name))
+    });
+    let mode = if args.iter().any(|a| a == "--float") { Mode::Float } else { Mode::Grid };
+    let rules = match option("--rules").as_deref() {
+        Some("1") => 1,
+        Some("2") | None => 2,
+        Some(other) => usage_error(&format!("bad --rules value {:?}", other))
+    };
+    let export = Export { rules, csv: option("--csv"), svg: option("--svg") };
 
-    let result2 = run_program2(&program);
-    println!("Distance by second rules: {}", (result2.x + result2.y).abs());
+    let contents = fs::read_to_string("input.txt").unwrap();
+    let program = program_from_contents(&contents, mode).unwrap();
+    match mode {
+        Mode::Grid => report::<Vec2>(&program, export),
+        Mode::Float => report::<FVec2>(&program, export)
+    }
 }
 
 #[test]
@@ -94,11 +286,85 @@ fn test_sample() {
     F7
     R90
     F11";
-    let program = program_from_contents(SAMPLE);
+    let program = program_from_contents(SAMPLE, Mode::Grid).unwrap();
 
-    let result1 = run_program1(&program);
+    let result1: Vec2 = run_program1(&program);
     assert_eq!(result1.x + result1.y, 25);
+    assert_eq!(manhattan(&result1), 25.0);
 
-    let result2 = run_program2(&program);
+    let result2: Vec2 = run_program2(&program);
     assert_eq!(result2.x + result2.y, 286);
+    assert_eq!(manhattan(&result2), 286.0);
+}
+
+#[test]
+fn test_trajectory() {
+    const SAMPLE: &str = "\
+    F10
+    N3
+    F7
+    R90
+    N15
+    F11";
+    let program = program_from_contents(SAMPLE, Mode::Grid).unwrap();
+
+    // Ending up north-east, where x and y have opposite signs.
+    let trajectory: Trajectory<Vec2> = trajectory1(&program);
+    assert_eq!(trajectory.len(), 7);
+    assert_eq!(trajectory[4], Pose { ship: Vec2::new(17, -3), waypoint: Vec2::new(0, 1) });
+    assert_eq!(trajectory[6].ship, Vec2::new(17, -7));
+    assert_eq!(manhattan(&trajectory[6].ship), 24.0);
+
+    let trajectory: Trajectory<Vec2> = trajectory2(&program);
+    let mut csv = Vec::new();
+    write_csv(&mut csv, &program, &trajectory).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    assert_eq!(csv.lines().nth(1), Some("0,,,0,0,10,-1"));
+    assert_eq!(csv.lines().nth(4), Some("3,F,7,170,-38,10,-4"));
+    assert_eq!(csv.lines().last(), Some("6,F,11,214,-93,4,-5"));
+
+    let mut svg = Vec::new();
+    write_svg(&mut svg, &trajectory).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    assert!(svg.contains(r#"<polyline points="0,0 100,-10 100,-10 170,-38 170,-38 170,-38 214,-93""#));
+}
+
+#[test]
+fn test_angles() {
+    assert_eq!(program_from_contents("F10\nX3", Mode::Grid).unwrap_err(),
+               ParseError::UnknownAction { line: 2, action: 'X' });
+    assert_eq!(program_from_contents("F1.5", Mode::Grid).unwrap_err(),
+               ParseError::BadValue { line: 1, value: "1.5".to_string() });
+    assert_eq!(program_from_contents("Finf", Mode::Float).unwrap_err(),
+               ParseError::BadValue { line: 1, value: "inf".to_string() });
+    assert_eq!(program_from_contents("R45", Mode::Grid).unwrap_err(),
+               ParseError::BadAngle { line: 1, degrees: 45 });
+
+    // Turns beyond a full circle or backwards reduce to the right thing.
+    assert_eq!(rotate_vector(&Vec2::new(1, 0), 450), Vec2::new(0, -1));
+    assert_eq!(rotate_vector(&Vec2::new(1, 0), -90), Vec2::new(0, 1));
+
+    // Float mode agrees with the grid on right angles...
+    let program = program_from_contents("F10\nN3\nF7\nR90\nF11\nL270\nW4", Mode::Float).unwrap();
+    let grid: Vec2 = run_program2(&program);
+    let float: FVec2 = run_program2(&program);
+    assert!((float - grid.xy().into()).length() < 1e-9);
+
+    // ...and handles the rest: sailing the sides of an equilateral triangle
+    // comes back to the start.
+    let program = program_from_contents("F10\nL120\nF10\nL120\nF10", Mode::Float).unwrap();
+    let ship: FVec2 = run_program1(&program);
+    assert!(manhattan(&ship) < 1e-9);
+    let trajectory: Trajectory<FVec2> = trajectory1(&program);
+    let (x, y) = trajectory[3].ship.xy();
+    assert!((x - 5.0).abs() < 1e-9 && (y + 75f64.sqrt()).abs() < 1e-9);
+
+    // Fractional distances and angles are kept rather than truncated.
+    let program = program_from_contents("F1.5\nL22.5\nN0.25", Mode::Float).unwrap();
+    let ship: FVec2 = run_program1(&program);
+    assert!((ship - FVec2::new(1.5, -0.25)).length() < 1e-9);
+    let program = program_from_contents("L22.5\nF2", Mode::Float).unwrap();
+    let ship: FVec2 = run_program1(&program);
+    let angle = 22.5f64.to_radians();
+    assert!((ship - FVec2::new(2.0 * angle.cos(), -2.0 * angle.sin())).length() < 1e-9);
 }
@@ -0,0 +1,87 @@
+// Chinese Remainder Theorem over u128, for moduli which need not be pairwise
+// coprime. Moduli and their lcm must fit in an i128, so that the extended
+// Euclid coefficients do.
+
+// Returns (g, x, y) such that a*x + b*y = g = gcd(a, b).
+pub fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    (old_r, old_x, old_y)
+}
+
+pub fn mod_inverse(a: u128, m: u128) -> Option<u128> {
+    let (g, x, _) = ext_gcd((a % m) as i128, m as i128);
+    if g != 1 {
+        return None;
+    }
+    Some(x.rem_euclid(m as i128) as u128)
+}
+
+fn add_mod(a: u128, b: u128, m: u128) -> u128 {
+    if a >= m - b { a - (m - b) } else { a + b }
+}
+
+// Double-and-add, so that nothing overflows however large the operands.
+fn mul_mod(a: u128, b: u128, m: u128) -> u128 {
+    let (mut a, mut b, mut result) = (a % m, b, 0);
+    while b > 0 {
+        if b & 1 == 1 {
+            result = add_mod(result, a, m);
+        }
+        a = add_mod(a, a, m);
+        b >>= 1;
+    }
+    result
+}
+
+// Combine x ≡ a1 (mod m1) and x ≡ a2 (mod m2) into a single congruence
+// modulo lcm(m1, m2), or None if they contradict each other.
+pub fn merge((a1, m1): (u128, u128), (a2, m2): (u128, u128)) -> Option<(u128, u128)> {
+    let (g, _, _) = ext_gcd(m1 as i128, m2 as i128);
+    let g = g as u128;
+    let (a1, a2) = (a1 % m1, a2 % m2);
+    if a1 % g != a2 % g {
+        return None;
+    }
+    // x = a1 + m1 * k, where (m1 / g) * k ≡ (a2 - a1) / g (mod m2 / g).
+    let reduced = m2 / g;
+    let lcm = m1 * reduced;
+    if reduced == 1 {
+        return Some((a1, lcm));
+    }
+    let diff = if a2 >= a1 { (a2 - a1) / g } else { reduced - ((a1 - a2) / g) % reduced };
+    let k = mul_mod(diff, mod_inverse(m1 / g, reduced)?, reduced);
+    Some((add_mod(a1, mul_mod(m1, k, lcm), lcm), lcm))
+}
+
+// The smallest non-negative x satisfying every (residue, modulus) pair, and
+// the modulus it repeats with; None if the congruences are inconsistent.
+pub fn crt(congruences: &[(u128, u128)]) -> Option<(u128, u128)> {
+    congruences.iter().try_fold((0, 1), |acc, &c| merge(acc, c))
+}
+
+#[test]
+fn test_crt() {
+    assert_eq!(ext_gcd(240, 46), (2, -9, 47));
+    assert_eq!(mod_inverse(3, 7), Some(5));
+    assert_eq!(mod_inverse(4, 8), None);
+
+    assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+    // Moduli sharing factors, consistent and not.
+    assert_eq!(crt(&[(2, 6), (4, 8)]), Some((20, 24)));
+    assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+    assert_eq!(crt(&[(5, 12), (5, 12), (1, 2)]), Some((5, 12)));
+
+    // Large coprime moduli whose product is well past u64.
+    let (m1, m2) = ((1u128 << 61) - 1, (1u128 << 31) - 1);
+    let (x, m) = crt(&[(12345, m1), (678, m2)]).unwrap();
+    assert_eq!(m, m1 * m2);
+    assert_eq!((x % m1, x % m2), (12345, 678));
+}
@@ -1,48 +1,115 @@
 use itertools::zip;
 use std::collections::{HashSet, VecDeque};
+use std::env;
 use std::fs;
+use std::process;
 use std::vec::Vec;
 
+mod crt;
 
-fn parse_contents(contents: &str) -> (u64, Vec<Option<u64>>) {
+#[derive(Debug, PartialEq)]
+enum ScheduleError {
+    MissingTimestamp,
+    MissingBuses,
+    BadTimestamp(String),
+    // The bus at the given position in the list isn't a positive number or x.
+    BadBus { index: usize, value: String }
+}
+
+fn parse_contents(contents: &str) -> Result<(u64, Vec<Option<u64>>), ScheduleError> {
     let mut lines = contents.lines();
-    (
-        lines.next().unwrap().trim().parse().unwrap(),
-        parse_list(&lines.next().unwrap())
-    )
+    let timestamp = lines.next().ok_or(ScheduleError::MissingTimestamp)?.trim();
+    let start_time = timestamp.parse()
+        .map_err(|_| ScheduleError::BadTimestamp(timestamp.to_string()))?;
+    let buses = parse_list(lines.next().ok_or(ScheduleError::MissingBuses)?)?;
+    Ok((start_time, buses))
 }
 
-fn parse_list(list: &str) -> Vec<Option<u64>> {
-    list.split(',').map(|s| {
+fn parse_list(list: &str) -> Result<Vec<Option<u64>>, ScheduleError> {
+    list.split(',').enumerate().map(|(index, s)| {
         match s.trim() {
-            "x" => None,
-            v => Some(v.parse().unwrap())
+            "x" => Ok(None),
+            v => match v.parse() {
+                Ok(bus) if bus > 0 => Ok(Some(bus)),
+                _ => Err(ScheduleError::BadBus { index, value: v.to_string() })
+            }
         }
     }).collect()
 }
 
 fn in_service_buses(all_buses: &[Option<u64>]) -> Vec<u64> {
-    all_buses.iter().copied().filter_map(|x| x).collect()
+    all_buses.iter().copied().flatten().collect()
+}
+
+// The first departure of a bus at or after the given time.
+fn next_departure(bus: u64, time: u64) -> u64 {
+    time + (bus - time % bus) % bus
 }
 
 fn next_buses(start_time: u64, buses: &[u64]) -> Vec<(u64, u64)> {
     buses.iter().map(|&bus| {
-        let busn = start_time as f64 / bus as f64;
-        let wait_time = ((busn.ceil() - busn) * bus as f64).round();
-        (wait_time as u64, bus as u64)
+        (next_departure(bus, start_time) - start_time, bus)
     }).collect::<Vec<_>>()
 }
 
+// Every in-service bus leaves at each multiple of its ID; slots in the
+// list without a bus are kept so that positions can be used as offsets.
+struct Timetable {
+    buses: Vec<Option<u64>>
+}
+
+impl Timetable {
+    fn new(buses: Vec<Option<u64>>) -> Self {
+        Self { buses }
+    }
+
+    fn in_service(&self) -> Vec<u64> {
+        in_service_buses(&self.buses)
+    }
+
+    // The earliest departure of any bus at or after the given time, as (time, bus).
+    fn next_departure(&self, time: u64) -> Option<(u64, u64)> {
+        next_buses(time, &self.in_service()).into_iter().min()
+            .map(|(wait_time, bus)| (time + wait_time, bus))
+    }
+
+    // All departures with start <= time < end, in order of time then bus.
+    fn departures_between(&self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let mut departures: Vec<(u64, u64)> = self.in_service().into_iter()
+            .flat_map(|bus| (next_departure(bus, start)..end).step_by(bus as usize).map(move |t| (t, bus)))
+            .collect();
+        departures.sort();
+        departures
+    }
+
+    // The first time t at which each (bus, offset) pair has the bus leaving at
+    // t + offset, or None if that never happens.
+    fn first_aligned(constraints: &[(u64, u64)]) -> Option<u128> {
+        let congruences: Vec<(u128, u128)> = constraints.iter().map(|&(bus, offset)| {
+            let bus = bus as u128;
+            ((bus - offset as u128 % bus) % bus, bus)
+        }).collect();
+        crt::crt(&congruences).map(|(time, _)| time)
+    }
+
+    // The puzzle's constraints: each bus leaving as many minutes after t as its
+    // position in the list.
+    fn sequence_time(&self) -> Option<u128> {
+        let constraints: Vec<(u64, u64)> = self.buses.iter().enumerate()
+            .filter_map(|(offset, bus)| bus.map(|bus| (bus, offset as u64)))
+            .collect();
+        Self::first_aligned(&constraints)
+    }
+}
+
 // This solution was cute, but not nearly performant enough.
-#[allow(dead_code)] 
+#[allow(dead_code)]
 fn find_sequence_naive(buses: &[Option<u64>]) -> u64 {
-    let in_service = in_service_buses(&buses);
+    let in_service = in_service_buses(buses);
 
     // This infinite iterator yields a hashmap of all bus departures per time increment.
     let mut schedule = (1..).map(|t| {
-        in_service.iter().copied().filter_map(|b| {
-            if t % b == 0 { Some(b) } else { None }
-        }).collect::<HashSet<u64>>()
+        in_service.iter().copied().filter(|b| t % b == 0).collect::<HashSet<u64>>()
     });
 
     let mut scanner: VecDeque<_> = schedule.by_ref().take(buses.len()).collect();
@@ -62,35 +129,43 @@ fn find_sequence_naive(buses: &[Option<u64>]) -> u64 {
     unreachable!();
 }
 
-// This version goes contraint by constraint, stepping through the search space
-// by the product of all previous constraints to find instances of the run that
-// are at least that long.
-fn find_sequence(buses: &[Option<u64>]) -> u64 {
-    let mut time = 0u64;
-    let mut search_step = 1u64;
-    for (bus_offset, bus_search_option) in buses.iter().enumerate() {
-        if let Some(bus_search) = bus_search_option {
-            for search_time in (time..).step_by(search_step as usize) {
-                if (search_time + bus_offset as u64) % bus_search == 0 {
-                    search_step *= bus_search;
-                    time = search_time;
-                    break;
-                }
-            }
+// Each bus gives a congruence: time + offset ≡ 0 (mod bus). Solve them all
+// at once with the CRT, which copes with IDs that share factors and reports
+// None when no time satisfies them all.
+fn find_sequence(buses: &[Option<u64>]) -> Option<u128> {
+    Timetable::new(buses.to_vec()).sequence_time()
+}
 
-        }
-    }
-    time
+const USAGE: &str = "usage: day-13 [--board MINUTES]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
 }
 
 fn main() {
+    // Pass --board MINUTES to list every departure in that long after arriving.
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| match args.get(i + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(&format!("{} needs a value", name))
+    });
+    let board: Option<u64> = option("--board").map(|minutes| {
+        minutes.parse().unwrap_or_else(|_| usage_error(&format!("bad --board value {:?}", minutes)))
+    });
+
     let contents = fs::read_to_string("input.txt").unwrap();
-    let (start_time, all_buses) = parse_contents(&contents);
-    let next = next_buses(start_time, &in_service_buses(&all_buses));
-    let (wait_time, bus) = next.iter().min().unwrap();
-    println!("Multiple: {:?}", wait_time * bus);
+    let (start_time, all_buses) = parse_contents(&contents).unwrap();
+    let timetable = Timetable::new(all_buses.clone());
+    let (time, bus) = timetable.next_departure(start_time).unwrap();
+    println!("Multiple: {:?}", (time - start_time) * bus);
+    if let Some(minutes) = board {
+        for (time, bus) in timetable.departures_between(start_time, start_time + minutes) {
+            println!("{:>10}  bus {}", time, bus);
+        }
+    }
 
-    let sequence_time = find_sequence(&all_buses);
+    let sequence_time = find_sequence(&all_buses).unwrap();
     println!("Sequence Time: {:?}", sequence_time);
 }
 
@@ -100,21 +175,54 @@ fn test_sample() {
     const SAMPLE: &str = "939
     7,13,x,x,59,x,31,19";
 
-    let (start_time, all_buses) = parse_contents(&SAMPLE);
+    let (start_time, all_buses) = parse_contents(SAMPLE).unwrap();
     let buses = in_service_buses(&all_buses);
     let next = next_buses(start_time, &buses);
     let (wait_time, bus) = next.iter().min().unwrap();
     assert_eq!(wait_time * bus, 295);
     
-    assert_eq!(find_sequence(&all_buses), 1068781);
+    assert_eq!(find_sequence(&all_buses), Some(1068781));
     assert_eq!(find_sequence_naive(&all_buses), 1068781);
 }
 
 #[test]
 fn more_samples() {
-    assert_eq!(find_sequence(&parse_list("17,x,13,19")), 3417);
-    assert_eq!(find_sequence(&parse_list("67,7,59,61")), 754018);
-    assert_eq!(find_sequence(&parse_list("67,x,7,59,61")), 779210);
-    assert_eq!(find_sequence(&parse_list("67,7,x,59,61")), 1261476);
-    assert_eq!(find_sequence(&parse_list("1789,37,47,1889")), 1202161486);
+    assert_eq!(find_sequence(&parse_list("17,x,13,19").unwrap()), Some(3417));
+    assert_eq!(find_sequence(&parse_list("67,7,59,61").unwrap()), Some(754018));
+    assert_eq!(find_sequence(&parse_list("67,x,7,59,61").unwrap()), Some(779210));
+    assert_eq!(find_sequence(&parse_list("67,7,x,59,61").unwrap()), Some(1261476));
+    assert_eq!(find_sequence(&parse_list("1789,37,47,1889").unwrap()), Some(1202161486));
+}
+
+#[test]
+fn shared_factors() {
+    // 4 and 6 share a factor of 2, so odd offsets between them can never line up.
+    assert_eq!(find_sequence(&parse_list("4,x,6").unwrap()), Some(4));
+    assert_eq!(find_sequence(&parse_list("4,6").unwrap()), None);
+}
+
+#[test]
+fn timetable() {
+    const SAMPLE: &str = "939
+    7,13,x,x,59,x,31,19";
+    let (start_time, all_buses) = parse_contents(SAMPLE).unwrap();
+    let timetable = Timetable::new(all_buses);
+    assert_eq!(timetable.next_departure(start_time), Some((944, 59)));
+    assert_eq!(timetable.next_departure(944), Some((944, 59)));
+    assert_eq!(timetable.departures_between(936, 946),
+               vec![(936, 13), (938, 7), (944, 59), (945, 7)]);
+
+    // Large timestamps are exact; floats would lose the wait time here.
+    let big = 1u64 << 60;
+    assert_eq!(next_departure(7, big), big + 6);
+
+    assert_eq!(Timetable::first_aligned(&[(7, 0), (13, 1)]), Some(77));
+    assert_eq!(Timetable::first_aligned(&[(4, 0), (6, 1)]), None);
+    assert_eq!(timetable.sequence_time(), Some(1068781));
+
+    assert_eq!(parse_contents(""), Err(ScheduleError::MissingTimestamp));
+    assert_eq!(parse_contents("939"), Err(ScheduleError::MissingBuses));
+    assert_eq!(parse_contents("soon\n7,13"), Err(ScheduleError::BadTimestamp("soon".to_string())));
+    assert_eq!(parse_list("7,y,13"), Err(ScheduleError::BadBus { index: 1, value: "y".to_string() }));
+    assert_eq!(parse_list("7,0"), Err(ScheduleError::BadBus { index: 1, value: "0".to_string() }));
 }
@@ -1,10 +1,12 @@
 extern crate itertools;
 extern crate regex;
 
-use itertools::Itertools;
 use regex::Regex;
 use std::collections::HashMap;
+use std::env;
 use std::fs;
+use std::io::{self, Write};
+use std::process;
 use std::vec::Vec;
 
 #[derive(Debug)]
@@ -22,62 +24,82 @@ enum Instr {
     }
 }
 
-fn read_program(contents: &str) -> Vec<Instr> {
-    let mask_re = Regex::new(r"^mask = ([01X]+)").unwrap();
-    let mem_re = Regex::new(r"^mem\[([0-9]+)\] = ([0-9]+)").unwrap();
-    contents.trim().lines().map(|line| {
-        let trimmed = line.trim();
-        if let Some(mask_result) = mask_re.captures(trimmed) {
-            let mask_str = mask_result.get(1).unwrap().as_str();
-            let mask = u64::from_str_radix(
-                &mask_str.replace("1", "0").replace("X", "1"), 2).unwrap();
-            let value = u64::from_str_radix(&mask_str.replace("X", "0"), 2).unwrap();
-            Instr::Mask(Mask { mask, value })
-        } else if let Some(mem_result) = mem_re.captures(trimmed) {
-            let addr = mem_result.get(1).unwrap().as_str().parse().unwrap();
-            let value = mem_result.get(2).unwrap().as_str().parse().unwrap();
-            Instr::Mem { addr, value }
-        } else {
-            // All input lines should match one of the above cases.
-            unreachable!();
-        }
-    }).collect()
+#[derive(Debug, PartialEq)]
+enum ProgramError {
+    // A word width outside 1 to 64 bits.
+    Width(u32),
+    Syntax { line: usize },
+    MaskWidth { line: usize, expected: u32, found: usize },
+    // An address or value with bits set beyond the word width.
+    TooWide { line: usize, value: String },
+    MemBeforeMask { line: usize }
 }
 
-fn run_program(program: &[Instr]) -> HashMap<u64, u64> {
-    let mut memory = HashMap::new();
-    let mut current_mask: Option<&Mask> = None;
-    for instr in program.iter() {
-        match instr {
-            Instr::Mask(m) => current_mask = Some(&m),
-            Instr::Mem { addr, value } => {
-                let result = value & current_mask.unwrap().mask
-                                   | current_mask.unwrap().value;
-                memory.insert(*addr, result);
-            }
-        }
+// Parse a program for a machine with words of the given number of bits.
+fn read_program(contents: &str, width: u32) -> Result<Vec<Instr>, ProgramError> {
+    if width == 0 || width > 64 {
+        return Err(ProgramError::Width(width));
     }
-    memory
+    let mask_re = Regex::new(r"^mask = ([01X]+)$").unwrap();
+    let mem_re = Regex::new(r"^mem\[([0-9]+)\] = ([0-9]+)$").unwrap();
+    let fits = |line: usize, text: &str| -> Result<u64, ProgramError> {
+        match text.parse::<u64>() {
+            Ok(value) if width == 64 || value >> width == 0 => Ok(value),
+            _ => Err(ProgramError::TooWide { line, value: text.to_string() })
+        }
+    };
+    let mut seen_mask = false;
+    contents.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            if let Some(mask_result) = mask_re.captures(trimmed) {
+                let mask_str = mask_result.get(1).unwrap().as_str();
+                if mask_str.len() != width as usize {
+                    return Err(ProgramError::MaskWidth { line: line_number, expected: width, found: mask_str.len() });
+                }
+                let mask = u64::from_str_radix(
+                    &mask_str.replace('1', "0").replace('X', "1"), 2).unwrap();
+                let value = u64::from_str_radix(&mask_str.replace('X', "0"), 2).unwrap();
+                seen_mask = true;
+                Ok(Instr::Mask(Mask { mask, value }))
+            } else if let Some(mem_result) = mem_re.captures(trimmed) {
+                if !seen_mask {
+                    return Err(ProgramError::MemBeforeMask { line: line_number });
+                }
+                let addr = fits(line_number, mem_result.get(1).unwrap().as_str())?;
+                let value = fits(line_number, mem_result.get(2).unwrap().as_str())?;
+                Ok(Instr::Mem { addr, value })
+            } else {
+                Err(ProgramError::Syntax { line: line_number })
+            }
+        }).collect()
 }
 
+// Expanding every floating combination is exponential in the number of X's;
+// kept to check AddressDecoder against.
+#[cfg(test)]
 fn run_program_v2(program: &[Instr]) -> HashMap<u64, u64> {
+    use itertools::Itertools;
+
     let mut memory = HashMap::new();
     let mut current_mask: Option<&Mask> = None;
     let mut mask_offsets = Vec::new();
     for instr in program.iter() {
         match instr {
             Instr::Mask(m) => {
-                current_mask = Some(&m);
+                current_mask = Some(m);
                 // Break down the mask into a vector for each bit that is
                 // participating, then get a vector of all the sums of
                 // combinations of those bits as memory offsets.
-                let mask_bits = (0..36u64).filter_map(|bit| {
+                let mask_bits = (0..64u64).filter_map(|bit| {
                     let val = m.mask & (1 << bit);
                     if val == 0 { None } else { Some(val) }
                 }).collect::<Vec<_>>();
-                mask_offsets = (0..=mask_bits.len()).map(|len| {
+                mask_offsets = (0..=mask_bits.len()).flat_map(|len| {
                     mask_bits.iter().copied().combinations(len)
-                }).flatten().map(|group| {
+                }).map(|group| {
                     group.iter().sum::<u64>()
                 }).collect();
             }
@@ -95,14 +117,270 @@ fn run_program_v2(program: &[Instr]) -> HashMap<u64, u64> {
     memory
 }
 
+// A set of addresses, as fixed values for some bits with the rest floating.
+// Fixed bits under the floating mask are always zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pattern {
+    fixed: u64,
+    floating: u64
+}
+
+impl Pattern {
+    fn new(fixed: u64, floating: u64) -> Self {
+        Self { fixed: fixed & !floating, floating }
+    }
+
+    fn count(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    fn contains(&self, addr: u64) -> bool {
+        addr & !self.floating == self.fixed
+    }
+
+    fn intersects(&self, other: &Pattern) -> bool {
+        let both_fixed = !self.floating & !other.floating;
+        (self.fixed ^ other.fixed) & both_fixed == 0
+    }
+
+    fn intersection(&self, other: &Pattern) -> Option<Pattern> {
+        if self.intersects(other) {
+            Some(Pattern::new(self.fixed | other.fixed, self.floating & other.floating))
+        } else {
+            None
+        }
+    }
+
+    // Most significant bit first, as in the program's masks.
+    fn format(self, width: u32) -> String {
+        (0..width).rev().map(|bit| {
+            if self.floating & (1 << bit) != 0 { 'X' }
+            else if self.fixed & (1 << bit) != 0 { '1' }
+            else { '0' }
+        }).collect()
+    }
+
+    // The addresses in self but not in other, as disjoint patterns. For each
+    // bit that floats here but is fixed in other, split off the half which
+    // disagrees with other; whatever is left at the end lies inside other.
+    fn subtract(&self, other: &Pattern) -> Vec<Pattern> {
+        if !self.intersects(other) {
+            return vec![*self];
+        }
+        let mut pieces = Vec::new();
+        let mut rest = *self;
+        let mut split_bits = self.floating & !other.floating;
+        while split_bits != 0 {
+            let bit = split_bits & split_bits.wrapping_neg();
+            split_bits &= !bit;
+            let floating = rest.floating & !bit;
+            pieces.push(Pattern::new(rest.fixed | (!other.fixed & bit), floating));
+            rest = Pattern::new(rest.fixed | (other.fixed & bit), floating);
+        }
+        pieces
+    }
+}
+
+// Memory for the v2 decoder, holding each write as the pattern of addresses
+// it covers. Earlier writes are trimmed by later ones that overlap them, so
+// the stored patterns stay disjoint and nothing is ever expanded per address.
+#[derive(Default, Clone)]
+struct FloatingMemory {
+    writes: Vec<(Pattern, u64)>
+}
+
+impl FloatingMemory {
+    fn write(&mut self, pattern: Pattern, value: u64) {
+        self.writes = self.writes.iter()
+            .flat_map(|(existing, v)| existing.subtract(&pattern).into_iter().map(move |p| (p, *v)))
+            .collect();
+        self.writes.push((pattern, value));
+    }
+}
+
+// What a decoder writes into.
+trait Memory: Default {
+    fn read(&self, addr: u64) -> Option<u64>;
+    fn sum(&self) -> u128;
+    // Every write still in memory, as the pattern of addresses it covers, in
+    // address order.
+    fn patterns(&self) -> Vec<(Pattern, u64)>;
+
+    // One line per stored pattern.
+    fn dump<W: Write>(&self, out: &mut W, width: u32) -> io::Result<()> {
+        for (pattern, value) in self.patterns() {
+            writeln!(out, "{} = {}", pattern.format(width), value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Memory for HashMap<u64, u64> {
+    fn read(&self, addr: u64) -> Option<u64> {
+        self.get(&addr).copied()
+    }
+
+    fn sum(&self) -> u128 {
+        self.values().map(|&v| v as u128).sum()
+    }
+
+    fn patterns(&self) -> Vec<(Pattern, u64)> {
+        let mut writes: Vec<(Pattern, u64)> = self.iter().map(|(&addr, &v)| (Pattern::new(addr, 0), v)).collect();
+        writes.sort_by_key(|(p, _)| p.fixed);
+        writes
+    }
+}
+
+impl Memory for FloatingMemory {
+    fn read(&self, addr: u64) -> Option<u64> {
+        self.writes.iter().find(|(p, _)| p.contains(addr)).map(|(_, v)| *v)
+    }
+
+    fn sum(&self) -> u128 {
+        self.writes.iter().map(|(p, v)| p.count() * *v as u128).sum()
+    }
+
+    fn patterns(&self) -> Vec<(Pattern, u64)> {
+        let mut writes = self.writes.clone();
+        writes.sort_by_key(|(p, _)| (p.fixed, p.floating));
+        writes
+    }
+}
+
+// The addresses whose contents differ between the two memories, with "-" for
+// an address that was never written.
+fn diff<A: Memory, B: Memory, W: Write>(a: &A, b: &B, out: &mut W, width: u32) -> io::Result<()> {
+    let (a, b) = (a.patterns(), b.patterns());
+    let only = |a: &[(Pattern, u64)], b: &[(Pattern, u64)]| -> Vec<(Pattern, u64)> {
+        a.iter().flat_map(|&(pattern, value)| {
+            b.iter().fold(vec![pattern], |pieces, (other, _)| {
+                pieces.iter().flat_map(|p| p.subtract(other)).collect()
+            }).into_iter().map(move |p| (p, value))
+        }).collect()
+    };
+    let mut lines = Vec::new();
+    for (pa, va) in &a {
+        for (pb, vb) in &b {
+            match pa.intersection(pb) {
+                Some(p) if va != vb => lines.push((p, va.to_string(), vb.to_string())),
+                _ => ()
+            }
+        }
+    }
+    lines.extend(only(&a, &b).into_iter().map(|(p, v)| (p, v.to_string(), "-".to_string())));
+    lines.extend(only(&b, &a).into_iter().map(|(p, v)| (p, "-".to_string(), v.to_string())));
+    lines.sort_by_key(|(p, _, _)| (p.fixed, p.floating));
+    for (pattern, a, b) in lines {
+        writeln!(out, "{} {} -> {}", pattern.format(width), a, b)?;
+    }
+    Ok(())
+}
+
+// How a decoder chip applies the current mask to a memory write.
+trait Decoder {
+    type Memory: Memory;
+    fn write(&self, memory: &mut Self::Memory, mask: &Mask, addr: u64, value: u64);
+}
+
+// Version 1: the mask applies to the value, written to a single address.
+struct ValueDecoder;
+
+impl Decoder for ValueDecoder {
+    type Memory = HashMap<u64, u64>;
+    fn write(&self, memory: &mut Self::Memory, mask: &Mask, addr: u64, value: u64) {
+        memory.insert(addr, value & mask.mask | mask.value);
+    }
+}
+
+// Version 2: the mask applies to the address, with X bits floating.
+struct AddressDecoder;
+
+impl Decoder for AddressDecoder {
+    type Memory = FloatingMemory;
+    fn write(&self, memory: &mut Self::Memory, mask: &Mask, addr: u64, value: u64) {
+        memory.write(Pattern::new(addr | mask.value, mask.mask), value);
+    }
+}
+
+struct DockingComputer<D: Decoder> {
+    width: u32,
+    decoder: D,
+    memory: D::Memory
+}
+
+impl<D: Decoder> DockingComputer<D> {
+    fn new(width: u32, decoder: D) -> Self {
+        Self { width, decoder, memory: D::Memory::default() }
+    }
+
+    // Programs from read_program always set a mask before writing.
+    fn run(&mut self, program: &[Instr]) -> &D::Memory {
+        let mut current_mask: Option<&Mask> = None;
+        for instr in program.iter() {
+            match instr {
+                Instr::Mask(m) => current_mask = Some(m),
+                Instr::Mem { addr, value } => {
+                    self.decoder.write(&mut self.memory, current_mask.unwrap(), *addr, *value);
+                }
+            }
+        }
+        &self.memory
+    }
+
+    fn dump<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        self.memory.dump(out, self.width)
+    }
+}
+
+const USAGE: &str = "usage: day-14 [--width BITS] [--dump] [--diff] [--get ADDRESS]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
+}
+
 fn main() {
+    // Options: --width BITS for the word size, --dump to list each decoder's
+    // memory, --diff to show where the two decoders disagree, and --get
+    // ADDRESS to look up one address in both.
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| match args.get(i + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(&format!("{} needs a value", name))
+    });
+    let width: u32 = option("--width").map_or(36, |width| {
+        width.parse().unwrap_or_else(|_| usage_error(&format!("bad --width value {:?}", width)))
+    });
+    let get: Option<u64> = option("--get").map(|addr| {
+        addr.parse().unwrap_or_else(|_| usage_error(&format!("bad --get value {:?}", addr)))
+    });
+
     let contents = fs::read_to_string("input.txt").unwrap();
-    let program = read_program(&contents);
-    let memory = run_program(&program);
-    println!("Memory sum v1: {}", memory.values().sum::<u64>());
+    let program = read_program(&contents, width).unwrap_or_else(|error| {
+        eprintln!("input.txt: {:?}", error);
+        process::exit(1)
+    });
+    let mut v1 = DockingComputer::new(width, ValueDecoder);
+    println!("Memory sum v1: {}", v1.run(&program).sum());
+
+    let mut v2 = DockingComputer::new(width, AddressDecoder);
+    println!("Memory sum v2: {}", v2.run(&program).sum());
 
-    let memory2 = run_program_v2(&program);
-    println!("Memory sum v2: {}", memory2.values().sum::<u64>());
+    let stdout = io::stdout();
+    if args.iter().any(|a| a == "--dump") {
+        println!("v1:");
+        v1.dump(&mut stdout.lock()).unwrap();
+        println!("v2:");
+        v2.dump(&mut stdout.lock()).unwrap();
+    }
+    if args.iter().any(|a| a == "--diff") {
+        diff(&v1.memory, &v2.memory, &mut stdout.lock(), width).unwrap();
+    }
+    if let Some(addr) = get {
+        let show = |value: Option<u64>| value.map_or("-".to_string(), |v| v.to_string());
+        println!("v1 mem[{}] = {}", addr, show(v1.memory.read(addr)));
+        println!("v2 mem[{}] = {}", addr, show(v2.memory.read(addr)));
+    }
 }
 
 #[test]
@@ -114,9 +392,11 @@ fn test_sample_v1() {
     mem[8] = 0
     "#;
 
-    let program = read_program(SAMPLE);
-    let memory = run_program(&program);
-    assert_eq!(memory.values().sum::<u64>(), 165);
+    let program = read_program(SAMPLE, 36).unwrap();
+    let mut computer = DockingComputer::new(36, ValueDecoder);
+    assert_eq!(computer.run(&program).sum(), 165);
+    assert_eq!(computer.memory.read(7), Some(101));
+    assert_eq!(computer.memory.read(9), None);
 }
 
 #[test]
@@ -128,7 +408,91 @@ fn test_sample_v2() {
     mem[26] = 1
     "#;
 
-    let program = read_program(SAMPLE);
+    let program = read_program(SAMPLE, 36).unwrap();
     let memory = run_program_v2(&program);
     assert_eq!(memory.values().sum::<u64>(), 208);
+    assert_eq!(DockingComputer::new(36, AddressDecoder).run(&program).sum(), 208);
+}
+
+#[test]
+fn test_patterns() {
+    use rand::{Rng, SeedableRng};
+
+    // Random programs over a few low bits, so that the writes overlap a lot.
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    let mut next = |n: u64| rng.gen_range(0..n);
+    for _ in 0..50 {
+        let mut lines = Vec::new();
+        for _ in 0..8 {
+            let mask: String = (0..36).map(|bit| match bit {
+                0..=29 => '0',
+                _ => ['0', '1', 'X', 'X'][next(4) as usize]
+            }).collect();
+            lines.push(format!("mask = {}", mask));
+            for _ in 0..3 {
+                lines.push(format!("mem[{}] = {}", next(64), next(1000)));
+            }
+        }
+        let program = read_program(&lines.join("\n"), 36).unwrap();
+        let expanded = run_program_v2(&program);
+        let mut computer = DockingComputer::new(36, AddressDecoder);
+        let patterns = computer.run(&program);
+        assert_eq!(patterns.sum(), expanded.values().sum::<u64>() as u128);
+        for addr in 0..64 {
+            assert_eq!(patterns.read(addr), expanded.get(&addr).copied());
+        }
+    }
+
+    // Thirty floating bits would be a billion inserts when expanded.
+    const SAMPLE: &str = r#"
+    mask = 000000XXXXXXXXXXXXXXXXXXXXXXXXXXXXXX
+    mem[0] = 3
+    mask = 0000000000000000000000000000000000X1
+    mem[0] = 5
+    "#;
+    let mut computer = DockingComputer::new(36, AddressDecoder);
+    let memory = computer.run(&read_program(SAMPLE, 36).unwrap());
+    assert_eq!(memory.sum(), 3 * ((1 << 30) - 2) + 5 * 2);
+    assert_eq!(memory.read(3), Some(5));
+    assert_eq!(memory.read(4), Some(3));
+}
+
+#[test]
+fn test_width() {
+    assert_eq!(read_program("mask = X1X\nmem[7] = 8", 3).unwrap_err(),
+               ProgramError::TooWide { line: 2, value: "8".to_string() });
+    assert_eq!(read_program("mask = X1X\nmem[8] = 1", 3).unwrap_err(),
+               ProgramError::TooWide { line: 2, value: "8".to_string() });
+    assert_eq!(read_program("mask = X1X1", 3).unwrap_err(),
+               ProgramError::MaskWidth { line: 1, expected: 3, found: 4 });
+    assert_eq!(read_program("mem[1] = 1", 3).unwrap_err(),
+               ProgramError::MemBeforeMask { line: 1 });
+    assert_eq!(read_program("mask = X1Y", 3).unwrap_err(),
+               ProgramError::Syntax { line: 1 });
+    assert_eq!(read_program("mask = X", 0).unwrap_err(), ProgramError::Width(0));
+    assert_eq!(read_program("mask = X", 65).unwrap_err(), ProgramError::Width(65));
+
+    const SAMPLE: &str = r#"
+    mask = X1X0
+    mem[0] = 3
+    mem[8] = 15
+    "#;
+    let program = read_program(SAMPLE, 4).unwrap();
+    let mut v1 = DockingComputer::new(4, ValueDecoder);
+    let mut v2 = DockingComputer::new(4, AddressDecoder);
+    assert_eq!(v1.run(&program).sum(), 6 + 14);
+    // Both writes land on the same four addresses under v2.
+    assert_eq!(v2.run(&program).sum(), 15 * 4);
+
+    let mut dump = Vec::new();
+    v2.dump(&mut dump).unwrap();
+    assert_eq!(String::from_utf8(dump).unwrap(), "X1X0 = 15\n");
+
+    let mut lines = Vec::new();
+    diff(&v1.memory, &v2.memory, &mut lines, 4).unwrap();
+    assert_eq!(String::from_utf8(lines).unwrap(), "\
+0000 6 -> -
+X1X0 - -> 15
+1000 14 -> -
+");
 }
@@ -3,13 +3,21 @@
 extern crate lazy_static;
 extern crate regex;
 extern crate itertools;
+extern crate serde;
+extern crate serde_json;
 
 use itertools::zip;
 use regex::Regex;
 use std::collections::HashSet;
+use std::env;
 use std::fs;
+use std::io;
 use std::ops::RangeInclusive;
 
+mod matching;
+mod report;
+use matching::perfect_matching;
+use report::Report;
 
 #[derive(Debug)]
 struct Rule {
@@ -18,32 +26,82 @@ struct Rule {
 }
 
 impl Rule {
+    // Rules are a name and any number of ranges joined by "or". A range may
+    // leave off either bound, so "-5" is everything up to 5 and "40-" is 40
+    // and above.
     fn parse(input: &str) -> Self {
         lazy_static! {
-            // This regex just hard-codes the inputs "a-b or c-d" format, but it
-            // would be easy to genericize it for arbitrary numbers of or-clauses.
-            static ref RE: Regex = Regex::new(
-                r"^([a-z ]+): ([0-9]+)-([0-9]+) or ([0-9]+)-([0-9]+)$").unwrap();
+            static ref RULE_RE: Regex = Regex::new(r"^([a-z ]+): (.+)$").unwrap();
+            static ref RANGE_RE: Regex = Regex::new(r"^([0-9]*)-([0-9]*)$").unwrap();
         }
-        let caps = RE.captures(input).unwrap();
+        let caps = RULE_RE.captures(input).unwrap();
         let name = caps.get(1).unwrap().as_str().to_string();
-        let cap_num = |n| caps.get(n).unwrap().as_str().parse::<u16>().unwrap();
-        let ranges = vec![
-            cap_num(2)..=cap_num(3),
-            cap_num(4)..=cap_num(5)
-        ];
+        let ranges = caps.get(2).unwrap().as_str().split(" or ").map(|clause| {
+            assert!(clause.trim() != "-", "range needs at least one bound");
+            let range_caps = RANGE_RE.captures(clause.trim()).unwrap();
+            let bound = |n, default| match range_caps.get(n).unwrap().as_str() {
+                "" => default,
+                num => num.parse::<u16>().unwrap()
+            };
+            bound(1, u16::MIN)..=bound(2, u16::MAX)
+        }).collect();
         Self { name, ranges }
     }
 
-    fn check(self: &Self, value: u16) -> bool {
+    fn check(&self, value: u16) -> bool {
         self.ranges.iter().any(|r| r.contains(&value))
     }
+
+    // How far value is from the nearest of the rule's ranges, zero if it fits.
+    fn distance(&self, value: u16) -> u16 {
+        self.ranges.iter().map(|r| {
+            if value < *r.start() {
+                r.start() - value
+            } else {
+                value.saturating_sub(*r.end())
+            }
+        }).min().unwrap_or(u16::MAX)
+    }
+}
+
+// Every rule's ranges merged into a sorted list of disjoint intervals, so that
+// checking a value against all the rules at once is a binary search.
+struct RuleSet {
+    intervals: Vec<RangeInclusive<u16>>
+}
+
+impl RuleSet {
+    fn compile(rules: &[Rule]) -> Self {
+        let mut ranges: Vec<&RangeInclusive<u16>> = rules.iter()
+            .flat_map(|rule| rule.ranges.iter())
+            .filter(|range| !range.is_empty())
+            .collect();
+        ranges.sort_by_key(|range| range.start());
+        let mut intervals: Vec<RangeInclusive<u16>> = Vec::new();
+        for range in ranges {
+            match intervals.last_mut() {
+                // Overlapping or touching, so extend the last interval.
+                Some(last) if *range.start() <= last.end().saturating_add(1) => {
+                    if range.end() > last.end() {
+                        *last = *last.start()..=*range.end();
+                    }
+                }
+                _ => intervals.push(range.clone())
+            }
+        }
+        Self { intervals }
+    }
+
+    fn check(&self, value: u16) -> bool {
+        let after = self.intervals.partition_point(|interval| *interval.start() <= value);
+        after > 0 && *self.intervals[after - 1].end() >= value
+    }
 }
 
 type Ticket = Vec<u16>;
 
 fn parse_ticket(input: &str) -> Ticket {
-    input.split(",").map(|s| {
+    input.split(',').map(|s| {
         s.parse().unwrap()
     }).collect()
 }
@@ -72,66 +130,147 @@ fn parse_input(input: &str) -> (Vec<Rule>, Ticket, Vec<Ticket>) {
 // flatten result externally and then getting a slice of that. However, this was
 // good practice to understand what it looks like to generically pass an iterator
 // and properly manage the lifetimes of the referenced items.
-fn find_invalid_numbers<'a, I>(nums: I, rules: &[Rule]) -> Vec<u16> 
+fn find_invalid_numbers<'a, I>(nums: I, rule_set: &RuleSet) -> Vec<u16>
 where I: Iterator<Item = &'a u16> {
-    nums.filter_map(|&num| {
-        for rule in rules {
-            if rule.check(num) {
-                return None
-            }
-        }
-        Some(num)
-    }).collect()
+    nums.filter(|&&num| !rule_set.check(num)).copied().collect()
 }
 
-fn find_valid_tickets<'a>(tickets: &'a[Ticket], rules: &[Rule]) -> Vec<&'a Ticket> {
-    tickets.iter().filter_map(|ticket| {
-        if find_invalid_numbers(ticket.iter(), rules).is_empty() {
-            Some(ticket)
-        } else {
-            None
-        }
+fn find_valid_tickets<'a>(tickets: &'a[Ticket], rule_set: &RuleSet) -> Vec<&'a Ticket> {
+    tickets.iter().filter(|ticket| {
+        find_invalid_numbers(ticket.iter(), rule_set).is_empty()
     }).collect()
 }
 
-fn determine_fields<'a>(valid_tickets: &[Ticket], rules: &'a[Rule]) -> Vec<&'a str> {
-    // Precook a vector of hashsets for potentially-applicable rules for each field,
-    // initially all rules.
-    let field_rules = (0..valid_tickets[0].len()).map(|_x| {
-        rules.iter().map(|r| r.name).collect::<HashSet<_>>()
-    }).collect::<Vec<_>>();
+#[derive(Debug, PartialEq)]
+enum FieldError {
+    // Every nearby ticket has a value no rule allows, leaving nothing to go on.
+    NoValidTickets,
+    // There's no way to give every column a different field.
+    NoAssignment,
+    // These columns could trade fields with others and still fit every ticket.
+    Ambiguous { columns: Vec<usize> }
+}
 
+// Precook a vector of hashsets for potentially-applicable rules for each field,
+// initially all rules, and strike out any rule a ticket breaks. With no tickets
+// there are no fields to go on.
+fn candidate_rules(valid_tickets: &[&Ticket], rules: &[Rule]) -> Vec<HashSet<usize>> {
+    let columns = valid_tickets.first().map_or(0, |ticket| ticket.len());
+    let mut field_rules = vec![(0..rules.len()).collect::<HashSet<_>>(); columns];
     for ticket in valid_tickets.iter() {
         for (rules_set, num) in zip(field_rules.iter_mut(), ticket.iter()) {
-            for rule in rules {
-                if !rule.check(*num) {
-                    rules_set.remove(&rule.name);
-                }
+            rules_set.retain(|&rule| rules[rule].check(*num));
+        }
+    }
+    field_rules
+}
+
+// A field with a single candidate rule takes it, which removes it from every
+// other field; repeat until that stops making progress.
+fn eliminate(field_rules: &mut [HashSet<usize>]) -> Vec<Option<usize>> {
+    let mut assigned = vec![None; field_rules.len()];
+    while let Some(field) = (0..field_rules.len())
+            .find(|&f| assigned[f].is_none() && field_rules[f].len() == 1) {
+        let rule = *field_rules[field].iter().next().unwrap();
+        assigned[field] = Some(rule);
+        for (other, rules_set) in field_rules.iter_mut().enumerate() {
+            if other != field {
+                rules_set.remove(&rule);
             }
         }
     }
-    
-    Vec::new()
+    assigned
+}
+
+fn determine_fields<'a>(valid_tickets: &[&Ticket], rules: &'a [Rule]) -> Result<Vec<&'a str>, FieldError> {
+    if valid_tickets.is_empty() {
+        return Err(FieldError::NoValidTickets);
+    }
+    let mut field_rules = candidate_rules(valid_tickets, rules);
+    let assigned = eliminate(&mut field_rules);
+    if let Some(assigned) = assigned.into_iter().collect::<Option<Vec<usize>>>() {
+        return Ok(assigned.iter().map(|&rule| rules[rule].name.as_str()).collect());
+    }
+
+    // Elimination stalled, so find any matching of fields to rules, and then
+    // check that no field could be matched differently.
+    let adjacency: Vec<Vec<usize>> = field_rules.iter().map(|rules_set| {
+        let mut candidates: Vec<usize> = rules_set.iter().copied().collect();
+        candidates.sort_unstable();
+        candidates
+    }).collect();
+    let matching = perfect_matching(&adjacency, rules.len()).ok_or(FieldError::NoAssignment)?;
+    let ambiguous: Vec<usize> = (0..adjacency.len()).filter(|&field| {
+        let mut without = adjacency.clone();
+        without[field].retain(|&rule| rule != matching[field]);
+        perfect_matching(&without, rules.len()).is_some()
+    }).collect();
+    if !ambiguous.is_empty() {
+        return Err(FieldError::Ambiguous { columns: ambiguous });
+    }
+    Ok(matching.iter().map(|&rule| rules[rule].name.as_str()).collect())
+}
+
+fn departure_product(fields: &[&str], my_ticket: &[u16]) -> u64 {
+    zip(fields, my_ticket)
+        .filter(|(field, _)| field.starts_with("departure"))
+        .map(|(_, &value)| value as u64)
+        .product()
 }
 
 fn main() {
+    // Options: --report to show how the fields were resolved, with --json to
+    // give it as JSON rather than a table.
+    let args: Vec<String> = env::args().collect();
     let input = fs::read_to_string("input.txt").unwrap();
     let (rules, my_ticket, tickets) = parse_input(&input);
-    let invalid = find_invalid_numbers(tickets.iter().flatten(), &rules);
+    let rule_set = RuleSet::compile(&rules);
+    let invalid = find_invalid_numbers(tickets.iter().flatten(), &rule_set);
     println!("Sum of invalid numbers: {}", invalid.iter().sum::<u16>());
 
-    let valid_tickets = find_valid_tickets(&tickets, &rules);
-    let fields = determine_fields(&valid_tickets, &rules);
-    println!("{:?}", fields);
+    if args.iter().any(|a| a == "--report") {
+        let report = Report::new(&rules, &tickets);
+        let stdout = io::stdout();
+        if args.iter().any(|a| a == "--json") {
+            report.write_json(&mut stdout.lock()).unwrap();
+        } else {
+            report.write_table(&mut stdout.lock()).unwrap();
+        }
+    }
+
+    let valid_tickets = find_valid_tickets(&tickets, &rule_set);
+    let fields = determine_fields(&valid_tickets, &rules).unwrap();
+    println!("Departure fields product: {}", departure_product(&fields, &my_ticket));
 }
 
 #[test]
 fn test_rule() {
     let rule = Rule::parse("test: 1-3 or 5-7");
     assert_eq!(rule.name, "test");
-    assert_eq!(rule.check(3), true);
-    assert_eq!(rule.check(4), false);
-    assert_eq!(rule.check(5), true);
+    assert!(rule.check(3));
+    assert!(!rule.check(4));
+    assert!(rule.check(5));
+
+    let rule = Rule::parse("open ended: -2 or 4-4 or 9-");
+    assert_eq!(rule.ranges, vec![0..=2, 4..=4, 9..=u16::MAX]);
+    assert!(rule.check(0));
+    assert!(!rule.check(3));
+    assert!(rule.check(65535));
+}
+
+#[test]
+fn test_rule_set() {
+    let rules = vec![
+        Rule::parse("a: 10-20 or 40-50"),
+        Rule::parse("b: 15-25 or 60-"),
+        Rule::parse("c: 26-30"),
+        Rule::parse("d: 45-47 or 5-5")
+    ];
+    let rule_set = RuleSet::compile(&rules);
+    assert_eq!(rule_set.intervals, vec![5..=5, 10..=30, 40..=50, 60..=u16::MAX]);
+    for value in 0..=u16::MAX {
+        assert_eq!(rule_set.check(value), rules.iter().any(|rule| rule.check(value)), "{}", value);
+    }
 }
 
 #[test]
@@ -150,10 +289,52 @@ fn test_sample() {
     55,2,20
     38,6,12"#;
 
-    let (rules, my_ticket, tickets) = parse_input(SAMPLE);
-    let invalid_nums = find_invalid_numbers(tickets.iter().flatten(), &rules);
+    let (rules, _, tickets) = parse_input(SAMPLE);
+    let rule_set = RuleSet::compile(&rules);
+    let invalid_nums = find_invalid_numbers(tickets.iter().flatten(), &rule_set);
     assert_eq!(invalid_nums.iter().sum::<u16>(), 71);
 
-    let valid_tickets = find_valid_tickets(&tickets, &rules);
+    let valid_tickets = find_valid_tickets(&tickets, &rule_set);
     assert_eq!(valid_tickets.len(), 1);
 }
+
+#[test]
+fn test_fields() {
+    const SAMPLE: &str = r#"
+    class: 0-1 or 4-19
+    departure row: 0-5 or 8-19
+    seat: 0-13 or 16-19
+
+    your ticket:
+    11,12,13
+
+    nearby tickets:
+    3,9,18
+    15,1,5
+    5,14,9"#;
+
+    let (rules, my_ticket, tickets) = parse_input(SAMPLE);
+    let valid_tickets = find_valid_tickets(&tickets, &RuleSet::compile(&rules));
+    let fields = determine_fields(&valid_tickets, &rules).unwrap();
+    assert_eq!(fields, vec!["departure row", "class", "seat"]);
+    assert_eq!(departure_product(&fields, &my_ticket), 11);
+
+    // Three columns which all fit both of two rules.
+    let rules = vec![Rule::parse("a: 1-2 or 5-6"), Rule::parse("b: 1-3 or 7-8")];
+    let ticket = vec![1, 2, 1];
+    assert_eq!(determine_fields(&[&ticket], &rules), Err(FieldError::NoAssignment));
+
+    // Elimination stalls on the first two columns, which can swap fields; the
+    // third is fixed by the matching even so.
+    let rules = vec![Rule::parse("a: 1-2 or 5-6"), Rule::parse("b: 1-2 or 7-8"),
+                     Rule::parse("c: 1-5 or 7-8")];
+    let ticket = vec![1, 2, 3];
+    assert_eq!(determine_fields(&[&ticket], &rules),
+               Err(FieldError::Ambiguous { columns: vec![0, 1] }));
+
+    // Every nearby ticket has a value outside all the rules.
+    let tickets = vec![vec![1, 9], vec![9, 2]];
+    let valid_tickets = find_valid_tickets(&tickets, &RuleSet::compile(&rules));
+    assert!(candidate_rules(&valid_tickets, &rules).is_empty());
+    assert_eq!(determine_fields(&valid_tickets, &rules), Err(FieldError::NoValidTickets));
+}
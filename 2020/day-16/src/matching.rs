@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+
+// Hopcroft–Karp maximum bipartite matching. adjacency[u] lists the right-hand
+// vertices (in 0..right) that left vertex u may be paired with; the result
+// gives each left vertex's partner, if it has one.
+pub fn hopcroft_karp(adjacency: &[Vec<usize>], right: usize) -> Vec<Option<usize>> {
+    let mut match_left: Vec<Option<usize>> = vec![None; adjacency.len()];
+    let mut match_right: Vec<Option<usize>> = vec![None; right];
+    let mut dist = vec![0usize; adjacency.len()];
+    while layer(adjacency, &match_left, &match_right, &mut dist) {
+        for u in 0..adjacency.len() {
+            if match_left[u].is_none() {
+                augment(u, adjacency, &mut match_left, &mut match_right, &mut dist);
+            }
+        }
+    }
+    match_left
+}
+
+// Breadth-first from the free left vertices, labelling each left vertex with
+// its alternating-path distance; true if some free right vertex is reachable.
+fn layer(adjacency: &[Vec<usize>], match_left: &[Option<usize>], match_right: &[Option<usize>],
+         dist: &mut [usize]) -> bool {
+    let mut queue = VecDeque::new();
+    for (u, partner) in match_left.iter().enumerate() {
+        if partner.is_none() {
+            dist[u] = 0;
+            queue.push_back(u);
+        } else {
+            dist[u] = usize::MAX;
+        }
+    }
+    let mut found = false;
+    while let Some(u) = queue.pop_front() {
+        for &v in &adjacency[u] {
+            match match_right[v] {
+                None => found = true,
+                Some(w) if dist[w] == usize::MAX => {
+                    dist[w] = dist[u] + 1;
+                    queue.push_back(w);
+                }
+                _ => ()
+            }
+        }
+    }
+    found
+}
+
+// Depth-first along the layers from u, flipping the first augmenting path found.
+fn augment(u: usize, adjacency: &[Vec<usize>], match_left: &mut [Option<usize>],
+           match_right: &mut [Option<usize>], dist: &mut [usize]) -> bool {
+    for &v in &adjacency[u] {
+        let advances = match match_right[v] {
+            None => true,
+            Some(w) => dist[w] == dist[u] + 1 && augment(w, adjacency, match_left, match_right, dist)
+        };
+        if advances {
+            match_left[u] = Some(v);
+            match_right[v] = Some(u);
+            return true;
+        }
+    }
+    // Dead end; don't revisit it in this phase.
+    dist[u] = usize::MAX;
+    false
+}
+
+// A matching which pairs off every left vertex, if there is one.
+pub fn perfect_matching(adjacency: &[Vec<usize>], right: usize) -> Option<Vec<usize>> {
+    hopcroft_karp(adjacency, right).into_iter().collect()
+}
+
+#[test]
+fn test_matching() {
+    // Greedy in order would give 0-0 and then strand 1.
+    let adjacency = vec![vec![0, 1], vec![0], vec![1, 2]];
+    assert_eq!(perfect_matching(&adjacency, 3), Some(vec![1, 0, 2]));
+
+    let adjacency = vec![vec![0, 1], vec![0, 1], vec![1]];
+    assert_eq!(perfect_matching(&adjacency, 2), None);
+    assert_eq!(hopcroft_karp(&adjacency, 2).iter().filter(|m| m.is_some()).count(), 2);
+
+    // More right vertices than left is fine.
+    let adjacency = vec![vec![3], vec![3, 4]];
+    assert_eq!(perfect_matching(&adjacency, 5), Some(vec![3, 4]));
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use std::io::{self, Write};
+
+use crate::{candidate_rules, determine_fields, eliminate, find_valid_tickets};
+use crate::{FieldError, Rule, RuleSet, Ticket};
+
+// A rule an invalid value nearly fitted, and by how much it missed.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NearMiss {
+    pub rule: String,
+    pub distance: u16
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InvalidValue {
+    // Index into the nearby tickets.
+    pub ticket: usize,
+    pub column: usize,
+    pub value: u16,
+    pub closest: Vec<NearMiss>
+}
+
+// Everything that goes into resolving the fields, for working out why an
+// input doesn't resolve.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Report {
+    pub invalid: Vec<InvalidValue>,
+    // The rules still possible for each column once elimination stalls.
+    pub candidates: Vec<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mapping: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>
+}
+
+impl Report {
+    pub fn new(rules: &[Rule], tickets: &[Ticket]) -> Self {
+        let rule_set = RuleSet::compile(rules);
+        let invalid = tickets.iter().enumerate().flat_map(|(ticket, values)| {
+            let rule_set = &rule_set;
+            values.iter().enumerate()
+                .filter(move |(_, &value)| !rule_set.check(value))
+                .map(move |(column, &value)| InvalidValue {
+                    ticket, column, value, closest: closest_rules(rules, value)
+                })
+        }).collect();
+
+        let valid_tickets = find_valid_tickets(tickets, &rule_set);
+        let mut field_rules = candidate_rules(&valid_tickets, rules);
+        eliminate(&mut field_rules);
+        let candidates = field_rules.iter().map(|rules_set| {
+            let mut names: Vec<String> = rules_set.iter().map(|&rule| rules[rule].name.clone()).collect();
+            names.sort();
+            names
+        }).collect();
+
+        let (mapping, error) = match determine_fields(&valid_tickets, rules) {
+            Ok(fields) => (Some(fields.iter().map(|f| f.to_string()).collect()), None),
+            Err(FieldError::NoValidTickets) =>
+                (None, Some("no nearby ticket is valid".to_string())),
+            Err(FieldError::NoAssignment) =>
+                (None, Some("no way to give every column a different field".to_string())),
+            Err(FieldError::Ambiguous { columns }) =>
+                (None, Some(format!("columns {:?} could take more than one field", columns)))
+        };
+        Self { invalid, candidates, mapping, error }
+    }
+
+    pub fn write_table<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "Invalid values:")?;
+        writeln!(out, "{:>6} {:>6} {:>6}  closest rules", "ticket", "column", "value")?;
+        for invalid in &self.invalid {
+            let closest: Vec<String> = invalid.closest.iter()
+                .map(|near| format!("{} (off by {})", near.rule, near.distance))
+                .collect();
+            writeln!(out, "{:>6} {:>6} {:>6}  {}", invalid.ticket, invalid.column, invalid.value,
+                     closest.join(", "))?;
+        }
+
+        writeln!(out, "\nCandidates after elimination:")?;
+        for (column, names) in self.candidates.iter().enumerate() {
+            writeln!(out, "{:>6}  {}", column, names.join(", "))?;
+        }
+
+        match (&self.mapping, &self.error) {
+            (Some(mapping), _) => {
+                writeln!(out, "\nMapping:")?;
+                for (column, name) in mapping.iter().enumerate() {
+                    writeln!(out, "{:>6}  {}", column, name)?;
+                }
+            }
+            (None, Some(error)) => writeln!(out, "\nNo mapping: {}", error)?,
+            (None, None) => ()
+        }
+        Ok(())
+    }
+
+    pub fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut *out, self)?;
+        writeln!(out)
+    }
+}
+
+// The rules that value missed by the least, in rule order.
+fn closest_rules(rules: &[Rule], value: u16) -> Vec<NearMiss> {
+    let best = rules.iter().map(|rule| rule.distance(value)).min();
+    rules.iter()
+        .filter(|rule| Some(rule.distance(value)) == best)
+        .map(|rule| NearMiss { rule: rule.name.clone(), distance: rule.distance(value) })
+        .collect()
+}
+
+#[test]
+fn test_report() {
+    const SAMPLE: &str = r#"
+    class: 0-1 or 4-19
+    row: 0-5 or 8-19
+    seat: 0-13 or 16-19
+
+    your ticket:
+    11,12,13
+
+    nearby tickets:
+    3,9,18
+    15,1,5
+    5,14,9
+    20,6,7"#;
+
+    let (rules, _, tickets) = crate::parse_input(SAMPLE);
+    let report = Report::new(&rules, &tickets);
+    assert_eq!(report.invalid, vec![InvalidValue {
+        ticket: 3, column: 0, value: 20, closest: vec![
+            NearMiss { rule: "class".to_string(), distance: 1 },
+            NearMiss { rule: "row".to_string(), distance: 1 },
+            NearMiss { rule: "seat".to_string(), distance: 1 }
+        ]
+    }]);
+    assert_eq!(report.candidates, vec![vec!["row"], vec!["class"], vec!["seat"]]);
+    assert_eq!(report.mapping, Some(vec!["row".to_string(), "class".to_string(), "seat".to_string()]));
+
+    let mut table = Vec::new();
+    report.write_table(&mut table).unwrap();
+    assert_eq!(String::from_utf8(table).unwrap(), "\
+Invalid values:
+ticket column  value  closest rules
+     3      0     20  class (off by 1), row (off by 1), seat (off by 1)
+
+Candidates after elimination:
+     0  row
+     1  class
+     2  seat
+
+Mapping:
+     0  row
+     1  class
+     2  seat
+");
+
+    // Two columns that fit both rules, so elimination gets nowhere.
+    let rules = vec![Rule::parse("a: 1-5"), Rule::parse("b: 2-9")];
+    let tickets = vec![vec![2, 3], vec![4, 10]];
+    let report = Report::new(&rules, &tickets);
+    assert_eq!(report.invalid[0].closest, vec![NearMiss { rule: "b".to_string(), distance: 1 }]);
+    assert_eq!(report.candidates, vec![vec!["a", "b"], vec!["a", "b"]]);
+    let mut json = Vec::new();
+    report.write_json(&mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(json["error"], "columns [0, 1] could take more than one field");
+    assert_eq!(json["invalid"][0]["column"], 1);
+    assert!(json.get("mapping").is_none());
+
+    // No nearby ticket is valid, so there are no columns to report on.
+    let tickets = vec![vec![0, 3], vec![10, 4]];
+    let report = Report::new(&rules, &tickets);
+    assert_eq!(report.invalid.len(), 2);
+    assert!(report.candidates.is_empty());
+    assert_eq!(report.mapping, None);
+    assert_eq!(report.error, Some("no nearby ticket is valid".to_string()));
+    let mut table = Vec::new();
+    report.write_table(&mut table).unwrap();
+    assert!(String::from_utf8(table).unwrap().ends_with("\nNo mapping: no nearby ticket is valid\n"));
+}
@@ -0,0 +1,185 @@
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+
+use crate::{MessageRule, RuleId, RuleMap};
+
+#[derive(Debug, PartialEq)]
+pub enum CompileError {
+    // The rule refers back to itself, so has no finite regex.
+    Recursive { rule: RuleId },
+    MissingRule(RuleId),
+    // The pattern came out bigger than the regex crate will build.
+    TooLarge
+}
+
+// A regex matching exactly the messages the start rule matches, for rule sets
+// without recursion.
+pub fn compile(rules: &RuleMap, start: &str) -> Result<Regex, CompileError> {
+    check_references(rules, start)?;
+    check_acyclic(rules, start, &mut Vec::new(), &mut Vec::new())?;
+    let mut unroller = Unroller::new(rules);
+    let pattern = unroller.pattern(start, None)?.0;
+    build(pattern.as_deref())
+}
+
+// As compile, but recursive rules are unrolled as deeply as messages of up to
+// max_len characters could need, so the regex agrees with the rules for any
+// message no longer than that.
+pub fn compile_bounded(rules: &RuleMap, start: &str, max_len: usize) -> Result<Regex, CompileError> {
+    check_references(rules, start)?;
+    let mut unroller = Unroller::new(rules);
+    let pattern = unroller.pattern(start, Some(max_len))?.0;
+    build(pattern.as_deref())
+}
+
+fn build(pattern: Option<&str>) -> Result<Regex, CompileError> {
+    // A rule which can't match anything still needs a regex, so use one which
+    // never does.
+    let pattern = format!("^(?:{})$", pattern.unwrap_or(r"[^\s\S]"));
+    RegexBuilder::new(&pattern)
+        .size_limit(1 << 26)
+        .build()
+        .map_err(|_| CompileError::TooLarge)
+}
+
+fn check_references(rules: &RuleMap, start: &str) -> Result<(), CompileError> {
+    let referenced = rules.values().flat_map(|rule| match rule {
+        MessageRule::Choice(choices) => choices.iter().flatten().map(|id| id.as_str()).collect(),
+        MessageRule::Text(_) => Vec::new()
+    });
+    match Some(start).into_iter().chain(referenced).find(|&id| !rules.contains_key(id)) {
+        Some(id) => Err(CompileError::MissingRule(id.to_string())),
+        None => Ok(())
+    }
+}
+
+fn check_acyclic<'r>(rules: &'r RuleMap, id: &'r str, path: &mut Vec<&'r str>,
+                     done: &mut Vec<&'r str>) -> Result<(), CompileError> {
+    if path.contains(&id) {
+        return Err(CompileError::Recursive { rule: id.to_string() });
+    }
+    if done.contains(&id) {
+        return Ok(());
+    }
+    path.push(id);
+    if let MessageRule::Choice(choices) = &rules[id] {
+        for step in choices.iter().flatten() {
+            check_acyclic(rules, step, path, done)?;
+        }
+    }
+    path.pop();
+    done.push(id);
+    Ok(())
+}
+
+// The length of the shortest message each rule matches; rules which only
+// match infinitely long messages are left out.
+fn min_lengths(rules: &RuleMap) -> HashMap<&str, usize> {
+    let mut lengths: HashMap<&str, usize> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for (id, rule) in rules.iter() {
+            let length = match rule {
+                MessageRule::Text(text) => Some(text.len()),
+                MessageRule::Choice(choices) => choices.iter().filter_map(|sequence| {
+                    sequence.iter().map(|step| lengths.get(step.as_str()).copied()).sum::<Option<usize>>()
+                }).min()
+            };
+            if let Some(length) = length {
+                if lengths.get(id.as_str()).is_none_or(|&old| length < old) {
+                    lengths.insert(id, length);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return lengths;
+        }
+    }
+}
+
+// Builds regex source for a rule, given how many characters of the message it
+// may use, or None for no limit. A None pattern is a rule with nothing left
+// that it can match.
+struct Unroller<'r> {
+    rules: &'r RuleMap,
+    min_lengths: HashMap<&'r str, usize>,
+    memo: HashMap<(&'r str, Option<usize>), Option<String>>,
+    stack: Vec<(&'r str, Option<usize>)>
+}
+
+impl<'r> Unroller<'r> {
+    fn new(rules: &'r RuleMap) -> Self {
+        Self { rules, min_lengths: min_lengths(rules), memo: HashMap::new(), stack: Vec::new() }
+    }
+
+    // Every step uses at least one character, so the budget shrinks down any
+    // recursion through a sequence. A rule reached again with the same budget
+    // (as in "1: 2" and "2: 1 | 3") adds nothing the other alternatives don't,
+    // so that branch is dropped; as with the Matcher, results which depended
+    // on a dropped branch are only valid further up, and aren't memoized.
+    fn pattern(&mut self, id: &'r str, budget: Option<usize>) -> Result<(Option<String>, usize), CompileError> {
+        let key = (id, budget);
+        if let Some(pattern) = self.memo.get(&key) {
+            return Ok((pattern.clone(), usize::MAX));
+        }
+        if let Some(depth) = self.stack.iter().position(|&k| k == key) {
+            return Ok((None, depth));
+        }
+        let min_length = match self.min_lengths.get(id) {
+            Some(&length) => length,
+            None => return Ok((None, usize::MAX))
+        };
+        if budget.is_some_and(|budget| min_length > budget) {
+            return Ok((None, usize::MAX));
+        }
+
+        let depth = self.stack.len();
+        self.stack.push(key);
+        let result = self.expand(id, budget);
+        self.stack.pop();
+        let (pattern, lowest) = result?;
+        if lowest >= depth {
+            self.memo.insert(key, pattern.clone());
+        }
+        Ok((pattern, lowest))
+    }
+
+    fn expand(&mut self, id: &'r str, budget: Option<usize>) -> Result<(Option<String>, usize), CompileError> {
+        let rules = self.rules;
+        let choices = match &rules[id] {
+            MessageRule::Text(text) => return Ok((Some(regex::escape(text)), usize::MAX)),
+            MessageRule::Choice(choices) => choices
+        };
+        let mut lowest = usize::MAX;
+        let mut alternatives = Vec::new();
+        'sequences: for sequence in choices.iter() {
+            let lengths: Option<Vec<usize>> = sequence.iter()
+                .map(|step| self.min_lengths.get(step.as_str()).copied())
+                .collect();
+            let lengths = match lengths {
+                Some(lengths) => lengths,
+                None => continue
+            };
+            let total: usize = lengths.iter().sum();
+            let mut parts = Vec::new();
+            for (step, &length) in sequence.iter().zip(lengths.iter()) {
+                // Whatever the rest of the sequence doesn't need at minimum.
+                let step_budget = budget.map(|budget| budget.saturating_sub(total - length));
+                let (pattern, reentered) = self.pattern(step, step_budget)?;
+                lowest = lowest.min(reentered);
+                match pattern {
+                    Some(pattern) => parts.push(pattern),
+                    None => continue 'sequences
+                }
+            }
+            alternatives.push(parts.concat());
+        }
+        let pattern = match alternatives.len() {
+            0 => None,
+            1 => alternatives.pop(),
+            _ => Some(format!("(?:{})", alternatives.join("|")))
+        };
+        Ok((pattern, lowest))
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::{MessageRule, RuleId, RuleMap};
+
+#[derive(Debug, PartialEq)]
+pub struct MissingRule(pub RuleId);
+
+enum Production {
+    Alternatives(Vec<Vec<usize>>),
+    Text(String)
+}
+
+// A rule set indexed for Earley parsing, which handles any context-free
+// grammar: left or right recursion, ambiguity, and cycles of unit rules.
+pub struct Grammar {
+    ids: Vec<RuleId>,
+    index: HashMap<RuleId, usize>,
+    productions: Vec<Production>
+}
+
+// Part way through an alternative: rule and alternative, how many of its
+// symbols have matched so far, and the offset where the match began.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Item {
+    rule: usize,
+    alt: usize,
+    dot: usize,
+    origin: usize
+}
+
+// How a rule matched a span of the message. Text rules are the leaves.
+#[derive(Debug, PartialEq)]
+pub struct ParseTree {
+    pub rule: RuleId,
+    pub start: usize,
+    pub end: usize,
+    pub children: Vec<ParseTree>
+}
+
+impl Grammar {
+    pub fn new(rules: &RuleMap) -> Result<Self, MissingRule> {
+        let mut ids: Vec<RuleId> = rules.keys().cloned().collect();
+        ids.sort();
+        let index: HashMap<RuleId, usize> = ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+        let productions = ids.iter().map(|id| match &rules[id] {
+            MessageRule::Text(text) => Ok(Production::Text(text.clone())),
+            MessageRule::Choice(choices) => choices.iter().map(|sequence| {
+                sequence.iter()
+                    .map(|step| index.get(step).copied().ok_or_else(|| MissingRule(step.clone())))
+                    .collect()
+            }).collect::<Result<_, _>>().map(Production::Alternatives)
+        }).collect::<Result<_, _>>()?;
+        Ok(Self { ids, index, productions })
+    }
+
+    pub fn recognize(&self, start: &str, input: &str) -> bool {
+        match self.index.get(start).map(|&rule| (rule, &self.productions[rule])) {
+            Some((_, Production::Text(text))) => input == text,
+            Some((rule, _)) => self.complete_at(&self.chart(rule, input), rule, 0, input.len()),
+            None => false
+        }
+    }
+
+    pub fn parse(&self, start: &str, input: &str) -> Option<ParseTree> {
+        let &rule = self.index.get(start)?;
+        let chart = self.chart(rule, input);
+        self.tree(&chart, input, rule, 0, input.len(), &mut Vec::new())
+    }
+
+    fn alternatives(&self, rule: usize) -> &[Vec<usize>] {
+        match &self.productions[rule] {
+            Production::Alternatives(alternatives) => alternatives,
+            Production::Text(_) => &[]
+        }
+    }
+
+    // Whether rule matches the whole of input[start..end].
+    fn complete_at(&self, chart: &[HashSet<Item>], rule: usize, start: usize, end: usize) -> bool {
+        self.alternatives(rule).iter().enumerate().any(|(alt, sequence)| {
+            chart[end].contains(&Item { rule, alt, dot: sequence.len(), origin: start })
+        })
+    }
+
+    // The Earley sets: chart[k] holds every item which has matched input[origin..k].
+    // A text rule is scanned as soon as it's predicted, so has no items of its
+    // own. No alternative is empty, so every completion lands in a later set
+    // than the items waiting on it.
+    fn chart(&self, start: usize, input: &str) -> Vec<HashSet<Item>> {
+        let mut chart: Vec<HashSet<Item>> = vec![HashSet::new(); input.len() + 1];
+        let mut pending: Vec<Vec<Item>> = vec![Vec::new(); input.len() + 1];
+        let add = |chart: &mut Vec<HashSet<Item>>, pending: &mut Vec<Vec<Item>>, k: usize, item: Item| {
+            if chart[k].insert(item) {
+                pending[k].push(item);
+            }
+        };
+        for alt in 0..self.alternatives(start).len() {
+            add(&mut chart, &mut pending, 0, Item { rule: start, alt, dot: 0, origin: 0 });
+        }
+
+        for k in 0..=input.len() {
+            while let Some(item) = pending[k].pop() {
+                let sequence = &self.alternatives(item.rule)[item.alt];
+                if item.dot < sequence.len() {
+                    let next = sequence[item.dot];
+                    let advanced = Item { dot: item.dot + 1, ..item };
+                    match &self.productions[next] {
+                        Production::Text(text) => if input[k..].starts_with(text.as_str()) {
+                            add(&mut chart, &mut pending, k + text.len(), advanced);
+                        }
+                        Production::Alternatives(alternatives) => {
+                            for alt in 0..alternatives.len() {
+                                add(&mut chart, &mut pending, k, Item { rule: next, alt, dot: 0, origin: k });
+                            }
+                        }
+                    }
+                } else {
+                    let waiting: Vec<Item> = chart[item.origin].iter().filter(|i| {
+                        let sequence = &self.alternatives(i.rule)[i.alt];
+                        i.dot < sequence.len() && sequence[i.dot] == item.rule
+                    }).copied().collect();
+                    for waiting_item in waiting {
+                        add(&mut chart, &mut pending, k, Item { dot: waiting_item.dot + 1, ..waiting_item });
+                    }
+                }
+            }
+        }
+        chart
+    }
+
+    // Rebuild one derivation of rule over input[start..end] from the chart.
+    // Spans already being built further up are skipped, so unit cycles can't
+    // recurse forever.
+    fn tree(&self, chart: &[HashSet<Item>], input: &str, rule: usize, start: usize, end: usize,
+            building: &mut Vec<(usize, usize, usize)>) -> Option<ParseTree> {
+        let leaf = |children| ParseTree { rule: self.ids[rule].clone(), start, end, children };
+        match &self.productions[rule] {
+            Production::Text(text) => {
+                return if input[start..end] == *text { Some(leaf(Vec::new())) } else { None };
+            }
+            Production::Alternatives(_) if building.contains(&(rule, start, end)) => return None,
+            Production::Alternatives(_) => ()
+        }
+        building.push((rule, start, end));
+        let found = self.alternatives(rule).iter().enumerate().find_map(|(alt, sequence)| {
+            if !chart[end].contains(&Item { rule, alt, dot: sequence.len(), origin: start }) {
+                return None;
+            }
+            self.children(chart, input, rule, alt, sequence.len(), start, end, building)
+        });
+        building.pop();
+        found.map(leaf)
+    }
+
+    // Split input[start..end] across the first dot symbols of an alternative.
+    #[allow(clippy::too_many_arguments)]
+    fn children(&self, chart: &[HashSet<Item>], input: &str, rule: usize, alt: usize, dot: usize,
+                start: usize, end: usize, building: &mut Vec<(usize, usize, usize)>) -> Option<Vec<ParseTree>> {
+        if dot == 0 {
+            return if start == end { Some(Vec::new()) } else { None };
+        }
+        let symbol = self.alternatives(rule)[alt][dot - 1];
+        // Each symbol matches at least one character.
+        for mid in (start..end).rev() {
+            if !chart[mid].contains(&Item { rule, alt, dot: dot - 1, origin: start }) {
+                continue;
+            }
+            if let Some(last) = self.tree(chart, input, symbol, mid, end, building) {
+                if let Some(mut children) = self.children(chart, input, rule, alt, dot - 1, start, mid, building) {
+                    children.push(last);
+                    return Some(children);
+                }
+            }
+        }
+        None
+    }
+}
+
+// One node per line, indented by depth, with the text each rule matched.
+impl ParseTree {
+    fn write(&self, f: &mut fmt::Formatter, input: &str, depth: usize) -> fmt::Result {
+        writeln!(f, "{:indent$}{}: {:?}", "", self.rule, &input[self.start..self.end], indent = depth * 2)?;
+        for child in &self.children {
+            child.write(f, input, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    pub fn display<'a>(&'a self, input: &'a str) -> impl fmt::Display + 'a {
+        struct Display<'a>(&'a ParseTree, &'a str);
+        impl fmt::Display for Display<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.0.write(f, self.1, 0)
+            }
+        }
+        Display(self, input)
+    }
+}
@@ -1,102 +1,258 @@
 extern crate pest;
 #[macro_use]
 extern crate pest_derive;
+extern crate regex;
+extern crate tracing;
+extern crate tracing_subscriber;
 
 use pest::Parser;
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::process;
+use std::sync::{Arc, Mutex};
 use std::vec::Vec;
+use tracing::{debug, trace, Level};
+
+mod compile;
+mod earley;
+use compile::{compile, compile_bounded};
+use earley::Grammar;
 
 #[derive(Parser)]
 #[grammar = "rule.pest"]
 struct RuleParser;
 
+// Rules are named by any run of letters, digits and underscores; the puzzle
+// only uses numbers.
+type RuleId = String;
+
 #[derive(Debug)]
 enum MessageRule {
     // Outer vec is the choices, inner vec is the sequence
-    // of rule numbers within each choice branch.
-    Choice(Vec<Vec<u8>>),
-    Char(String)
+    // of rule ids within each choice branch.
+    Choice(Vec<Vec<RuleId>>),
+    // A quoted string of one or more characters.
+    Text(String)
 }
 
-type RuleMap = HashMap<u8, MessageRule>;
+type RuleMap = HashMap<RuleId, MessageRule>;
 
 impl MessageRule {
-    fn parse(input: &str) -> (u8, Self) {
-        let parsed_rule = RuleParser::parse(Rule::rule, &input).unwrap().next().unwrap(); 
+    fn parse(input: &str) -> (RuleId, Self) {
+        let parsed_rule = RuleParser::parse(Rule::rule, input).unwrap().next().unwrap();
         let mut rule_inner = parsed_rule.into_inner();
-        let num = rule_inner.next().unwrap().as_str().parse().unwrap();
+        let id = rule_inner.next().unwrap().as_str().to_string();
         let rule_contents = rule_inner.next().unwrap();
         let message_rule = match rule_contents.as_rule() {
             Rule::choice => MessageRule::Choice(rule_contents.into_inner().map(|seq|
-                seq.into_inner().map(|rule_num| rule_num.as_str().parse().unwrap()).collect()
+                seq.into_inner().map(|rule_id| rule_id.as_str().to_string()).collect()
             ).collect()),
-            Rule::char_quoted => MessageRule::Char(
+            Rule::text_quoted => MessageRule::Text(
                 rule_contents.into_inner().next().unwrap().as_str().to_string()
             ),
             _ => unreachable!()
         };
-        (num, message_rule)
-    }
-
-    // Returns the possible slices following the match or an empty set for no matches down this
-    // branch of the search tree.
-    fn check<'a>(self: &Self, rules: &RuleMap, remaining: &'a str) -> HashSet<&'a str> {
-        let mut remainders = HashSet::<&str>::new();
-        println!("> {:?} {:?}", &self, &remaining);
-        if remaining.len() > 0 {
-            match self {
-                MessageRule::Choice(choices) => {
-                    for sequence in choices.iter() {
-                        //remainders.extend(&MessageRule::check_sequence(
-                        //        &rules, &sequence, remaining));
-                        let s = MessageRule::check_sequence(&rules, &sequence, remaining);
-
-                        remainders.extend(&s);
-                        println!("xx {:?} {:?}", &remainders, &s);
+        (id, message_rule)
+    }
+}
+
+// Finds every way a rule can match at the start of an input, as the set of
+// offsets where each match ends. Results are memoized by rule and start.
+//
+// Left-recursive rules such as "8: 8 42 | 42" would recurse forever when
+// evaluated naively, so a rule which is re-entered at the offset where it is
+// already being evaluated gets the ends found for it so far, starting from
+// none, and is then re-evaluated until that set stops growing.
+//
+// Each evaluation emits trace events, and a matcher made with recording also
+// keeps them as a tree of Attempts.
+struct Matcher<'r, 'a> {
+    rules: &'r RuleMap,
+    input: &'a str,
+    memo: HashMap<(&'r str, usize), HashSet<usize>>,
+    stack: Vec<(&'r str, usize)>,
+    seeds: HashMap<(&'r str, usize), HashSet<usize>>,
+    recording: bool,
+    attempts: Vec<Attempt>
+}
+
+// One evaluation of a rule at an offset, and the evaluations it led to.
+#[derive(Debug)]
+struct Attempt {
+    rule: RuleId,
+    start: usize,
+    ends: Vec<usize>,
+    // Set when the ends weren't evaluated here but taken from an earlier result.
+    note: Option<&'static str>,
+    children: Vec<Attempt>
+}
+
+impl Attempt {
+    fn write<W: Write>(&self, out: &mut W, input: &str, depth: usize) -> io::Result<()> {
+        let ends = if self.ends.is_empty() {
+            "no match".to_string()
+        } else {
+            let matched: Vec<String> = self.ends.iter().map(|&end| format!("{:?}", &input[self.start..end])).collect();
+            matched.join(" | ")
+        };
+        write!(out, "{:indent$}{} @{}: {}", "", self.rule, self.start, ends, indent = depth * 2)?;
+        match self.note {
+            Some(note) => writeln!(out, " ({})", note)?,
+            None => writeln!(out)?
+        }
+        for child in &self.children {
+            child.write(out, input, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'r, 'a> Matcher<'r, 'a> {
+    fn new(rules: &'r RuleMap, input: &'a str) -> Self {
+        Self {
+            rules, input, memo: HashMap::new(), stack: Vec::new(), seeds: HashMap::new(),
+            recording: false, attempts: Vec::new()
+        }
+    }
+
+    fn recording(rules: &'r RuleMap, input: &'a str) -> Self {
+        Self { recording: true, ..Self::new(rules, input) }
+    }
+
+    // The tree of evaluations behind the first call to remainders.
+    fn attempt(mut self) -> Option<Attempt> {
+        self.attempts.pop()
+    }
+
+    fn begin_attempt(&mut self, id: &str, start: usize) {
+        if self.recording {
+            self.attempts.push(Attempt {
+                rule: id.to_string(), start, ends: Vec::new(), note: None, children: Vec::new()
+            });
+        }
+    }
+
+    // Finish the innermost attempt, adding it to its parent's children.
+    fn end_attempt(&mut self, ends: &HashSet<usize>, note: Option<&'static str>) {
+        if !self.recording {
+            return;
+        }
+        let mut attempt = self.attempts.pop().unwrap();
+        attempt.ends = ends.iter().copied().collect();
+        attempt.ends.sort_unstable();
+        attempt.note = note;
+        match self.attempts.last_mut() {
+            Some(parent) => parent.children.push(attempt),
+            None => self.attempts.push(attempt)
+        }
+    }
+
+    // Returns the possible slices following the match, or an empty set for no match.
+    fn remainders(&mut self, id: &'r str) -> HashSet<&'a str> {
+        let input = self.input;
+        self.ends(id, 0).0.into_iter().map(|end| &input[end..]).collect()
+    }
+
+    // Along with the ends, returns the lowest depth on the stack of any rule
+    // which was re-entered during the evaluation, since results depending on
+    // a rule still being grown are provisional and can't be memoized.
+    fn ends(&mut self, id: &'r str, start: usize) -> (HashSet<usize>, usize) {
+        let key = (id, start);
+        let depth = self.stack.len();
+        trace!(rule = id, depth, remaining = &self.input[start..], "enter");
+        self.begin_attempt(id, start);
+        if let Some(ends) = self.memo.get(&key).cloned() {
+            trace!(rule = id, depth, ends = ?ends, "memoized");
+            self.end_attempt(&ends, Some("memoized"));
+            return (ends, usize::MAX);
+        }
+        if let Some(reentered) = self.stack.iter().position(|&k| k == key) {
+            let ends = self.seeds[&key].clone();
+            debug!(rule = id, depth, ends = ?ends, "re-entered, using the ends so far");
+            self.end_attempt(&ends, Some("re-entered, so the ends so far"));
+            return (ends, reentered);
+        }
+
+        self.stack.push(key);
+        self.seeds.insert(key, HashSet::new());
+        let mut lowest = usize::MAX;
+        loop {
+            // Only the final round of a re-evaluated rule goes in the tree.
+            if let Some(attempt) = self.attempts.last_mut() {
+                attempt.children.clear();
+            }
+            let (ends, reentered) = self.eval(id, start);
+            lowest = lowest.min(reentered);
+            let seed = self.seeds.get_mut(&key).unwrap();
+            let before = seed.len();
+            seed.extend(ends);
+            if seed.len() == before || reentered > depth {
+                break;
+            }
+        }
+        self.stack.pop();
+        let ends = self.seeds.remove(&key).unwrap();
+        trace!(rule = id, depth, ends = ?ends, "exit");
+        self.end_attempt(&ends, None);
+        if lowest < depth {
+            (ends, lowest)
+        } else {
+            self.memo.insert(key, ends.clone());
+            (ends, usize::MAX)
+        }
+    }
+
+    fn eval(&mut self, id: &'r str, start: usize) -> (HashSet<usize>, usize) {
+        let rules = self.rules;
+        let mut lowest = usize::MAX;
+        let ends = match &rules[id] {
+            MessageRule::Choice(choices) => {
+                let mut ends = HashSet::new();
+                for sequence in choices.iter() {
+                    // Each step of the sequence continues from every place the
+                    // previous step could have finished, in order so that
+                    // traces come out the same each time.
+                    let mut positions: BTreeSet<usize> = [start].iter().copied().collect();
+                    for step in sequence.iter() {
+                        let mut next = BTreeSet::new();
+                        for position in positions {
+                            let (step_ends, reentered) = self.ends(step, position);
+                            lowest = lowest.min(reentered);
+                            next.extend(step_ends);
+                        }
+                        positions = next;
                     }
+                    ends.extend(positions);
                 }
-                MessageRule::Char(ch) =>
-                    if remaining[0..1] == *ch {
-                        remainders.insert(&remaining[1..]);
-                    } 
+                ends
             }
-        }
-        println!("< {:?} {:?}", &self, &remainders);
-        return remainders;
-    }
-
-    // Similar to the above, but instead of taking a rule, this one takes a slice into a
-    // sequence. This is needed as a separate recursive function because there is branching
-    // possible within a sequence.
-    fn check_sequence<'a>(rules: &RuleMap, seq_steps: &[u8], remaining: &'a str) -> HashSet<&'a str> {
-        let mut remainders = HashSet::<&str>::new();
-        let rule_num = seq_steps[0];
-        let all_remaining_after_rule = rules[&rule_num].check(&rules, &remaining);
-        println!("{:?} {:?} {:?}", &seq_steps, &remaining, &all_remaining_after_rule);
-        for remaining_after_rule in all_remaining_after_rule.iter() {
-            if seq_steps.len() > 1 && remaining_after_rule.len() > 1 {
-                let s = MessageRule::check_sequence(&rules, &seq_steps[1..], &remaining_after_rule);
-                remainders.extend(&s);
-                println!("xxx {:?} {:?}", &remainders, &s);
-            } else if seq_steps.len() == 1 && remaining_after_rule.len() == 1 {
-                remainders.insert(&"");
+            MessageRule::Text(text) => {
+                if self.input[start..].starts_with(text.as_str()) {
+                    [start + text.len()].iter().copied().collect()
+                } else {
+                    HashSet::new()
+                }
             }
-        }
-        return remainders;
+        };
+        (ends, lowest)
     }
 }
 
 fn check_inputs<'a>(rules: &RuleMap, inputs: &'a[String]) -> Vec<&'a String> {
-    inputs.iter().filter_map(|input| {
-        let remaining_slices = rules[&0].check(&rules, input);
-        if remaining_slices.contains(&"") {
-            return Some(input);
-        }
-        None
+    inputs.iter().filter(|input| {
+        let matched = Matcher::new(rules, input).remainders("0").contains("");
+        debug!(message = input.as_str(), matched, "checked");
+        matched
     }).collect()
 }
 
+fn check_inputs_regex<'a>(regex: &Regex, inputs: &'a[String]) -> Vec<&'a String> {
+    inputs.iter().filter(|input| regex.is_match(input)).collect()
+}
+
 fn parse_content(content: &str) -> (RuleMap, Vec<String>) {
     let mut lines = content.trim().lines().map(|line| line.trim());
     let mut rules = HashMap::new();
@@ -120,11 +276,100 @@ fn update_rules(rule_map: &mut RuleMap) {
     }
 }
 
+// A handle on the trace file for each event, all sharing the one file.
+struct SharedFile(Arc<Mutex<File>>);
+
+impl Write for SharedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+// Only the matcher emits trace events, so asking for them checks the messages
+// with it. They go to stderr at -v (each message checked) or -vv (every rule
+// evaluated), or all of them to a file with --trace FILE; otherwise they're
+// off.
+fn init_tracing(trace_file: Option<String>, args: &[String]) {
+    let level = if trace_file.is_some() || args.iter().any(|a| a == "-vv") {
+        Level::TRACE
+    } else if args.iter().any(|a| a == "-v") {
+        Level::DEBUG
+    } else {
+        return;
+    };
+    let builder = tracing_subscriber::fmt().with_max_level(level).with_target(false).without_time();
+    match trace_file {
+        Some(filename) => {
+            let file = Arc::new(Mutex::new(File::create(filename).unwrap()));
+            builder.with_writer(move || SharedFile(file.clone())).init();
+        }
+        None => builder.with_writer(io::stderr).init()
+    }
+}
+
+const USAGE: &str = "usage: day-19 [--earley | --matcher] [--parse MESSAGE] [--explain MESSAGE] \
+                     [-v | -vv] [--trace FILE]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
+}
+
 fn main() {
+    // Options: --earley or --matcher to check the messages with the general
+    // parser or the recursive matcher rather than regexes, --parse MESSAGE to
+    // show how the updated rules parse a message, and --explain MESSAGE to show
+    // everything the matcher tried for it. See init_tracing for the rest.
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| match args.get(i + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(&format!("{} needs a value", name))
+    });
+    let (parse, explain) = (option("--parse"), option("--explain"));
+    let earley = args.iter().any(|a| a == "--earley");
+    let trace_file = option("--trace");
+    let tracing = trace_file.is_some() || args.iter().any(|a| a == "-v" || a == "-vv");
+    if earley && tracing {
+        usage_error("-v, -vv and --trace follow the matcher, so can't be used with --earley");
+    }
+    init_tracing(trace_file, &args);
+    let matcher = tracing || args.iter().any(|a| a == "--matcher");
+
     let (mut rule_map, inputs) = parse_content(&fs::read_to_string("input.txt").unwrap());
-    println!("Matches by initial rules: {}", check_inputs(&rule_map, &inputs).len());
+    // The updated rules loop, so their regex is unrolled far enough for the
+    // longest message.
+    let max_len = inputs.iter().map(|input| input.len()).max().unwrap_or(0);
+    let count_matches = |rule_map: &RuleMap, recursive: bool| if earley {
+        let grammar = Grammar::new(rule_map).unwrap();
+        inputs.iter().filter(|input| grammar.recognize("0", input)).count()
+    } else if matcher {
+        check_inputs(rule_map, &inputs).len()
+    } else if recursive {
+        check_inputs_regex(&compile_bounded(rule_map, "0", max_len).unwrap(), &inputs).len()
+    } else {
+        check_inputs_regex(&compile(rule_map, "0").unwrap(), &inputs).len()
+    };
 
+    println!("Matches by initial rules: {}", count_matches(&rule_map, false));
     update_rules(&mut rule_map);
+    println!("Matches by updated rules: {}", count_matches(&rule_map, true));
+
+    if let Some(message) = parse {
+        let grammar = Grammar::new(&rule_map).unwrap();
+        match grammar.parse("0", &message) {
+            Some(tree) => print!("{}", tree.display(&message)),
+            None => println!("No match for {}", message)
+        }
+    }
+    if let Some(message) = explain {
+        let mut matcher = Matcher::recording(&rule_map, &message);
+        matcher.remainders("0");
+        matcher.attempt().unwrap().write(&mut io::stdout().lock(), &message, 0).unwrap();
+    }
 }
 
 #[test]
@@ -137,14 +382,16 @@ fn test_sample1() {
     4: "a"
     5: "b"
 
-    ababbb"#;
-    /*bababa
+    ababbb
+    bababa
     abbbab
     aaabbb
-    aaaabbb"#;*/
+    aaaabbb"#;
 
     let (rule_map, inputs) = parse_content(SAMPLE);
     assert_eq!(check_inputs(&rule_map, &inputs).len(), 2);
+    let regex = compile(&rule_map, "0").unwrap();
+    assert_eq!(check_inputs_regex(&regex, &inputs), check_inputs(&rule_map, &inputs));
 }
 
 #[test]
@@ -201,10 +448,27 @@ fn test_sample2() {
     let (mut rule_map, inputs) = parse_content(SAMPLE);
     assert_eq!(check_inputs(&rule_map, &inputs).len(), 3);
 
+    assert_eq!(check_inputs_regex(&compile(&rule_map, "0").unwrap(), &inputs).len(), 3);
+
     update_rules(&mut rule_map);
-    println!("{:?}", check_inputs(&rule_map, &inputs));
+    assert_eq!(check_inputs(&rule_map, &inputs).len(), 12);
+    assert_eq!(compile(&rule_map, "0").unwrap_err(), compile::CompileError::Recursive { rule: "8".to_string() });
+    let max_len = inputs.iter().map(|input| input.len()).max().unwrap();
+    let regex = compile_bounded(&rule_map, "0", max_len).unwrap();
+    assert_eq!(check_inputs_regex(&regex, &inputs), check_inputs(&rule_map, &inputs));
+
+    // The same loops written left-recursively match the same messages.
+    for rule_str in &["8: 8 42 | 42", "11: 42 31 | 42 11 31"] {
+        let (num, rule) = MessageRule::parse(rule_str);
+        rule_map.insert(num, rule);
+    }
+    assert_eq!(check_inputs(&rule_map, &inputs).len(), 12);
+    let regex = compile_bounded(&rule_map, "0", max_len).unwrap();
+    assert_eq!(check_inputs_regex(&regex, &inputs).len(), 12);
 
-    //assert_eq!(check_inputs(&rule_map, &inputs).len(), 12);
+    let grammar = Grammar::new(&rule_map).unwrap();
+    let matched: Vec<&String> = inputs.iter().filter(|input| grammar.recognize("0", input)).collect();
+    assert_eq!(matched, check_inputs(&rule_map, &inputs));
 }
 
 #[test]
@@ -246,6 +510,146 @@ fn test_sample3() {
 
     let (mut rule_map, inputs) = parse_content(SAMPLE);
 
+    assert!(check_inputs(&rule_map, &inputs).is_empty());
     update_rules(&mut rule_map);
-    println!("{:?}", check_inputs(&rule_map, &inputs));
+    assert_eq!(check_inputs(&rule_map, &inputs), vec!["aaaaabbaabaaaaababaa"]);
+}
+
+#[test]
+fn test_remainders() {
+    // Mutually left-recursive: 2 is one or more "ab", and 1 is 2 followed by
+    // an "a", or a lone "a".
+    const SAMPLE: &str = r#"
+    0: 2 3
+    1: 2 4 | 4
+    2: 1 3 | 4 3
+    3: "b"
+    4: "a"
+
+    ab"#;
+    let (rule_map, _) = parse_content(SAMPLE);
+    let mut matcher = Matcher::new(&rule_map, "ababab");
+    let mut remainders: Vec<&str> = matcher.remainders("2").into_iter().collect();
+    remainders.sort_unstable();
+    assert_eq!(remainders, vec!["", "ab", "abab"]);
+    let mut remainders: Vec<&str> = matcher.remainders("1").into_iter().collect();
+    remainders.sort_unstable();
+    assert_eq!(remainders, vec!["b", "bab", "babab"]);
+
+    let mut matcher = Matcher::new(&rule_map, "ababbab");
+    assert_eq!(matcher.remainders("0"), ["ab"].iter().copied().collect());
+    let mut matcher = Matcher::new(&rule_map, "baa");
+    assert!(matcher.remainders("0").is_empty());
+}
+
+#[test]
+fn test_compile_bounded() {
+    // A unit cycle between 1 and 2, and 3 matching a^n b^n.
+    const SAMPLE: &str = r#"
+    0: 1 3
+    1: 2 | 4
+    2: 1 | 5
+    3: 4 5 | 4 3 5
+    4: "a"
+    5: "b"
+
+    ab"#;
+    let (rule_map, _) = parse_content(SAMPLE);
+    let regex = compile_bounded(&rule_map, "0", 7).unwrap();
+    let messages: Vec<String> = (1..=7usize).flat_map(|len| {
+        (0..1u32 << len).map(move |bits| {
+            (0..len).map(|bit| if bits & (1 << bit) != 0 { 'b' } else { 'a' }).collect()
+        })
+    }).collect();
+    let expected = check_inputs(&rule_map, &messages);
+    assert_eq!(check_inputs_regex(&regex, &messages), expected);
+    assert_eq!(expected.len(), 6);
+    assert!(!regex.is_match("aaaabbbb"));
+
+    let mut rule_map = rule_map;
+    rule_map.remove("5");
+    assert_eq!(compile_bounded(&rule_map, "0", 7).unwrap_err(), compile::CompileError::MissingRule("5".to_string()));
+}
+
+#[test]
+fn test_earley() {
+    // Named rules, multi-character terminals, and an ambiguous, left-recursive
+    // expression grammar.
+    const SAMPLE: &str = r#"
+    expr: expr plus expr | term
+    term: term times factor | factor
+    factor: open expr close | number
+    number: digit | digit number
+    digit: zero | one | two
+    plus: " plus "
+    times: "*"
+    open: "("
+    close: ")"
+    zero: "0"
+    one: "1"
+    two: "2""#;
+    let (rule_map, _) = parse_content(SAMPLE);
+    let grammar = Grammar::new(&rule_map).unwrap();
+    assert!(grammar.recognize("expr", "1 plus 2*(10 plus 2) plus 0"));
+    assert!(grammar.recognize("number", "2021"));
+    assert!(!grammar.recognize("expr", "1 plus"));
+    assert!(!grammar.recognize("expr", "1plus2"));
+    assert!(!grammar.recognize("missing", "1"));
+    assert!(grammar.recognize("times", "*"));
+
+    let message = "12*(2)";
+    let tree = grammar.parse("term", message).unwrap();
+    assert_eq!((tree.rule.as_str(), tree.start, tree.end), ("term", 0, 6));
+    let rules: Vec<&str> = tree.children.iter().map(|child| child.rule.as_str()).collect();
+    assert_eq!(rules, vec!["term", "times", "factor"]);
+    assert_eq!(tree.display(message).to_string(), r#"term: "12*(2)"
+  term: "12"
+    factor: "12"
+      number: "12"
+        digit: "1"
+          one: "1"
+        number: "2"
+          digit: "2"
+            two: "2"
+  times: "*"
+  factor: "(2)"
+    open: "("
+    expr: "2"
+      term: "2"
+        factor: "2"
+          number: "2"
+            digit: "2"
+              two: "2"
+    close: ")"
+"#);
+    assert_eq!(grammar.parse("term", "12*"), None);
+
+    let mut rule_map = rule_map;
+    rule_map.remove("two");
+    assert_eq!(Grammar::new(&rule_map).err(), Some(earley::MissingRule("two".to_string())));
+}
+
+#[test]
+fn test_attempt() {
+    const SAMPLE: &str = r#"
+    0: 1 2
+    1: 1 3 | 3
+    2: "b"
+    3: "a"
+
+    aab"#;
+    let (rule_map, inputs) = parse_content(SAMPLE);
+    let mut matcher = Matcher::recording(&rule_map, &inputs[0]);
+    assert!(matcher.remainders("0").contains(""));
+    let mut out = Vec::new();
+    matcher.attempt().unwrap().write(&mut out, &inputs[0], 0).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), r#"0 @0: "aab"
+  1 @0: "a" | "aa"
+    1 @0: "a" | "aa" (re-entered, so the ends so far)
+    3 @1: "a" (memoized)
+    3 @2: no match
+    3 @0: "a" (memoized)
+  2 @1: no match
+  2 @2: "b"
+"#);
 }
@@ -1,46 +1,130 @@
 extern crate itertools;
 
 use itertools::zip;
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::fmt;
 use std::fs;
+use std::io::{self, Write};
 use std::vec::Vec;
 
-fn parse_decks(input: &str) -> Vec<VecDeque<u8>> {
-    let mut lines = input.trim().lines();
+// Returns the player names, from the "Player N:" headers, and their decks.
+fn parse_decks(input: &str) -> (Vec<String>, Vec<VecDeque<u8>>) {
+    let mut lines = input.trim().lines().map(|line| line.trim());
+    let mut names = Vec::new();
     let mut decks = Vec::new();
-    'next_deck: loop {
-        lines.next().unwrap();
+    'next_deck: while let Some(header) = lines.next() {
+        names.push(header.trim_end_matches(':').to_string());
         let mut deck = VecDeque::new();
         for line in lines.by_ref() {
             if line.is_empty() {
                 decks.push(deck);
                 continue 'next_deck;
             }
-            deck.push_back(line.trim().parse().unwrap())
+            deck.push_back(line.parse().unwrap())
         }
         decks.push(deck);
-        break;
     }
-    return decks;
+    (names, decks)
+}
+
+// Writes a round-by-round account of the games in the puzzle's format, if
+// there's anywhere to write it.
+struct Log<'w> {
+    out: Option<&'w mut dyn Write>,
+    names: &'w [String]
+}
+
+impl<'w> Log<'w> {
+    fn new(out: &'w mut dyn Write, names: &'w [String]) -> Self {
+        Self { out: Some(out), names }
+    }
+
+    fn off() -> Self {
+        Self { out: None, names: &[] }
+    }
+
+    fn enabled(&self) -> bool {
+        self.out.is_some()
+    }
+
+    fn line(&mut self, args: fmt::Arguments) {
+        if let Some(out) = &mut self.out {
+            out.write_fmt(args).unwrap();
+            out.write_all(b"\n").unwrap();
+        }
+    }
+
+    fn decks(&mut self, decks: &[VecDeque<u8>]) {
+        let names = self.names;
+        if self.enabled() {
+            for (player, deck) in decks.iter().enumerate() {
+                let cards: Vec<String> = deck.iter().map(|card| card.to_string()).collect();
+                self.line(format_args!("{}'s deck: {}", names[player], cards.join(", ")));
+            }
+        }
+    }
+
+    fn plays(&mut self, drawn_cards: &[Option<u8>]) {
+        let names = self.names;
+        if self.enabled() {
+            for (player, card) in drawn_cards.iter().enumerate() {
+                if let Some(card) = card {
+                    self.line(format_args!("{} plays: {}", names[player], card));
+                }
+            }
+        }
+    }
+
+    // The puzzle writes "player 2" mid-sentence.
+    fn lower_name(&self, player: usize) -> String {
+        self.names[player].replacen("Player", "player", 1)
+    }
+}
+
+// Each player still holding cards draws one; the others sit the round out.
+fn draw(decks: &mut [VecDeque<u8>]) -> Vec<Option<u8>> {
+    decks.iter_mut().map(|d| d.pop_front()).collect()
+}
+
+fn highest_card(drawn_cards: &[Option<u8>]) -> usize {
+    (0..drawn_cards.len()).max_by_key(|&player| drawn_cards[player]).unwrap()
+}
+
+// The winner's own card goes to the bottom of their deck first, and then the
+// rest from highest to lowest.
+fn collect_cards(decks: &mut [VecDeque<u8>], drawn_cards: &[Option<u8>], winner: usize) {
+    decks[winner].push_back(drawn_cards[winner].unwrap());
+    let mut others: Vec<u8> = drawn_cards.iter().enumerate()
+        .filter(|&(player, _)| player != winner)
+        .filter_map(|(_, &card)| card)
+        .collect();
+    others.sort_unstable_by(|a, b| b.cmp(a));
+    decks[winner].extend(others);
+}
+
+// The game is over once only one player has any cards left.
+fn sole_survivor(decks: &[VecDeque<u8>]) -> Option<usize> {
+    let mut holding = decks.iter().enumerate().filter(|(_, d)| !d.is_empty());
+    match (holding.next(), holding.next()) {
+        (Some((player, _)), None) => Some(player),
+        _ => None
+    }
 }
 
 // Returns Some(winner) if the game is over.
-fn take_turn<'a>(decks: &'a mut Vec<VecDeque<u8>>) -> Option<&'a VecDeque<u8>> {
-    let mut drawn_cards = decks.iter_mut().map(|d|
-        d.pop_front().unwrap()
-    ).collect::<Vec<_>>();
-    let (winner, loser) = if drawn_cards[0] > drawn_cards[1] {
-        (0, 1)
-    } else {
-        drawn_cards.reverse();
-        (1, 0)
-    };
-    decks[winner].extend(drawn_cards.iter());
-    if decks[loser].is_empty() {
-        Some(&decks[winner])
-    } else {
-        None
+fn take_turn(decks: &mut [VecDeque<u8>], round: usize, log: &mut Log) -> Option<usize> {
+    log.line(format_args!("-- Round {} --", round));
+    log.decks(decks);
+    let drawn_cards = draw(decks);
+    log.plays(&drawn_cards);
+    let winner = highest_card(&drawn_cards);
+    if log.enabled() {
+        let names = log.names;
+        log.line(format_args!("{} wins the round!\n", names[winner]));
     }
+    collect_cards(decks, &drawn_cards, winner);
+    sole_survivor(decks)
 }
 
 fn compute_score(deck: &VecDeque<u8>) -> u32 {
@@ -49,18 +133,117 @@ fn compute_score(deck: &VecDeque<u8>) -> u32 {
     ).sum()
 }
 
-fn play_game(mut decks: &mut Vec<VecDeque<u8>>) -> u32 {
-    loop {
-        if let Some(winning_deck) = take_turn(&mut decks) {
-            return compute_score(&winning_deck);
+// Plays Combat to the end, returning the winning player's index; decks is
+// left holding the final decks.
+fn play_game(decks: &mut [VecDeque<u8>], log: &mut Log) -> usize {
+    for round in 1.. {
+        if let Some(winner) = take_turn(decks, round, log) {
+            return winner;
         }
     }
+    unreachable!()
+}
+
+// Sub-game winners by the decks the sub-game started with. The same sub-games
+// come up over and over, from different rounds and different parent games.
+type GameMemo = HashMap<Vec<VecDeque<u8>>, usize>;
+
+// Everything shared between the games of a Recursive Combat match. Sub-games
+// found in the memo aren't played, so they'd be missing from the log; logging
+// turns memoization off to keep the log matching the puzzle's.
+struct RecursiveCombat<'l, 'w> {
+    memo: GameMemo,
+    games: usize,
+    log: &'l mut Log<'w>
+}
+
+impl<'l, 'w> RecursiveCombat<'l, 'w> {
+    fn new(log: &'l mut Log<'w>) -> Self {
+        Self { memo: GameMemo::new(), games: 0, log }
+    }
+
+    // Plays a game to the end, returning the winning player's index; decks is
+    // left holding the final decks.
+    fn play(&mut self, decks: &mut [VecDeque<u8>]) -> usize {
+        self.games += 1;
+        let game = self.games;
+        self.log.line(format_args!("=== Game {} ===\n", game));
+        let mut seen = HashSet::new();
+        for round in 1.. {
+            // A repeated position would repeat forever. The puzzle gives the
+            // game to player 1 then, and only has two players; with more, the
+            // game goes to the first player still holding cards, which for
+            // two players is always player 1.
+            if !seen.insert(decks.to_vec()) {
+                return decks.iter().position(|d| !d.is_empty()).unwrap();
+            }
+            self.log.line(format_args!("-- Round {} (Game {}) --", round, game));
+            self.log.decks(decks);
+            let drawn_cards = draw(decks);
+            self.log.plays(&drawn_cards);
+
+            let recurse = zip(decks.iter(), drawn_cards.iter())
+                .all(|(d, card)| card.is_none_or(|card| d.len() >= card as usize));
+            let winner = if recurse {
+                let sub_decks: Vec<VecDeque<u8>> = zip(decks.iter(), drawn_cards.iter())
+                    .map(|(d, card)| d.iter().take(card.unwrap_or(0) as usize).copied().collect())
+                    .collect();
+                self.log.line(format_args!("Playing a sub-game to determine the winner...\n"));
+                let winner = match self.memo.get(&sub_decks) {
+                    Some(&winner) => winner,
+                    None => {
+                        let winner = self.play(&mut sub_decks.clone());
+                        if !self.log.enabled() {
+                            self.memo.insert(sub_decks, winner);
+                        }
+                        winner
+                    }
+                };
+                self.log.line(format_args!("...anyway, back to game {}.", game));
+                winner
+            } else {
+                highest_card(&drawn_cards)
+            };
+            if self.log.enabled() {
+                let names = self.log.names;
+                self.log.line(format_args!("{} wins round {} of game {}!\n", names[winner], round, game));
+            }
+            collect_cards(decks, &drawn_cards, winner);
+            if let Some(winner) = sole_survivor(decks) {
+                if self.log.enabled() {
+                    let name = self.log.lower_name(winner);
+                    self.log.line(format_args!("The winner of game {} is {}!\n", game, name));
+                }
+                return winner;
+            }
+        }
+        unreachable!()
+    }
+}
+
+fn post_game(log: &mut Log, decks: &[VecDeque<u8>]) {
+    log.line(format_args!("== Post-game results =="));
+    log.decks(decks);
 }
 
 fn main() {
+    // Options: --log to write out every round of both games.
+    let args: Vec<String> = env::args().collect();
     let input = fs::read_to_string("input.txt").unwrap();
-    let mut decks = parse_decks(&input);
-    println!("Winning score: {}", play_game(&mut decks));
+    let (names, decks) = parse_decks(&input);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut log = if args.iter().any(|a| a == "--log") { Log::new(&mut out, &names) } else { Log::off() };
+
+    let mut game_decks = decks.clone();
+    let winner = play_game(&mut game_decks, &mut log);
+    post_game(&mut log, &game_decks);
+    println!("Winning score: {} ({})", compute_score(&game_decks[winner]), names[winner]);
+
+    let mut game_decks = decks;
+    let winner = RecursiveCombat::new(&mut log).play(&mut game_decks);
+    post_game(&mut log, &game_decks);
+    println!("Recursive winning score: {} ({})", compute_score(&game_decks[winner]), names[winner]);
 }
 
 #[test]
@@ -80,6 +263,165 @@ fn test_sample() {
     7
     10"#;
 
-    let mut decks = parse_decks(INPUT);
-    assert_eq!(play_game(&mut decks), 306);
+    let (names, mut decks) = parse_decks(INPUT);
+    assert_eq!(names, vec!["Player 1", "Player 2"]);
+    let winner = play_game(&mut decks, &mut Log::off());
+    assert_eq!(compute_score(&decks[winner]), 306);
+
+    let (_, mut decks) = parse_decks(INPUT);
+    assert_eq!(RecursiveCombat::new(&mut Log::off()).play(&mut decks), 1);
+    assert_eq!(decks[1], vec![7, 5, 6, 2, 4, 1, 10, 8, 9, 3]);
+    assert_eq!(compute_score(&decks[1]), 291);
+}
+
+#[test]
+fn test_infinite_game() {
+    const INPUT: &str = r#"
+    Player 1:
+    43
+    19
+
+    Player 2:
+    2
+    29
+    14"#;
+
+    let (_, mut decks) = parse_decks(INPUT);
+    assert_eq!(RecursiveCombat::new(&mut Log::off()).play(&mut decks), 0);
+}
+
+#[test]
+fn test_log() {
+    const INPUT: &str = r#"
+    Player 1:
+    9
+    2
+    6
+    3
+    1
+
+    Player 2:
+    5
+    8
+    4
+    7
+    10"#;
+
+    let (names, decks) = parse_decks(INPUT);
+    let mut out = Vec::new();
+    let mut game_decks = decks.clone();
+    let mut log = Log::new(&mut out, &names);
+    play_game(&mut game_decks, &mut log);
+    post_game(&mut log, &game_decks);
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("\
+-- Round 1 --
+Player 1's deck: 9, 2, 6, 3, 1
+Player 2's deck: 5, 8, 4, 7, 10
+Player 1 plays: 9
+Player 2 plays: 5
+Player 1 wins the round!
+
+-- Round 2 --
+Player 1's deck: 2, 6, 3, 1, 9, 5
+Player 2's deck: 8, 4, 7, 10
+Player 1 plays: 2
+Player 2 plays: 8
+Player 2 wins the round!
+"));
+    assert!(text.ends_with("\
+Player 2 wins the round!
+
+== Post-game results ==
+Player 1's deck: \n\
+Player 2's deck: 3, 2, 10, 6, 8, 5, 9, 4, 7, 1
+"));
+    assert_eq!(text.matches("-- Round").count(), 29);
+
+    let mut out = Vec::new();
+    let mut game_decks = decks;
+    let mut log = Log::new(&mut out, &names);
+    RecursiveCombat::new(&mut log).play(&mut game_decks);
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.contains("\
+-- Round 9 (Game 1) --
+Player 1's deck: 4, 9, 8, 5, 2
+Player 2's deck: 3, 10, 1, 7, 6
+Player 1 plays: 4
+Player 2 plays: 3
+Playing a sub-game to determine the winner...
+
+=== Game 2 ===
+
+-- Round 1 (Game 2) --
+Player 1's deck: 9, 8, 5, 2
+Player 2's deck: 10, 1, 7
+Player 1 plays: 9
+Player 2 plays: 10
+Player 2 wins round 1 of game 2!
+"));
+    assert!(text.contains("\
+The winner of game 2 is player 2!
+
+...anyway, back to game 1.
+Player 2 wins round 9 of game 1!
+"));
+    assert!(text.ends_with("The winner of game 1 is player 2!\n\n"));
+}
+
+#[test]
+fn test_three_players() {
+    const INPUT: &str = r#"
+    Alice:
+    1
+    2
+
+    Bob:
+    3
+    7
+
+    Carol:
+    5
+    4
+    6"#;
+
+    let (names, decks) = parse_decks(INPUT);
+    assert_eq!(names, vec!["Alice", "Bob", "Carol"]);
+    let mut first = decks.clone();
+    assert_eq!(take_turn(&mut first, 1, &mut Log::off()), None);
+    // Carol's own card goes first, then the others from highest to lowest.
+    assert_eq!(first, vec![vec![2], vec![7], vec![4, 6, 5, 3, 1]]);
+
+    let mut game_decks = decks.clone();
+    assert_eq!(play_game(&mut game_decks, &mut Log::off()), 1);
+    assert_eq!(game_decks, vec![vec![], vec![4, 6, 2, 5, 3, 7, 1], vec![]]);
+
+    // In round 5 Bob plays 2 and Carol 3 with enough cards behind them, and
+    // Bob takes the sub-game while Alice is out.
+    let mut game_decks = decks;
+    assert_eq!(RecursiveCombat::new(&mut Log::off()).play(&mut game_decks), 1);
+    assert_eq!(game_decks, vec![vec![], vec![2, 6, 3, 5, 4, 7, 1], vec![]]);
+}
+
+#[test]
+fn test_three_player_repeat() {
+    const INPUT: &str = r#"
+    Alice:
+    1
+
+    Bob:
+    2
+    3
+    4
+    5
+
+    Carol:
+    6
+    7"#;
+
+    // Alice is out after the first round, and rounds 5 to 12 come back round
+    // to the decks after round 4, so Bob as the first player left takes it.
+    let (_, mut decks) = parse_decks(INPUT);
+    assert_eq!(RecursiveCombat::new(&mut Log::off()).play(&mut decks), 1);
+    assert_eq!(decks, vec![vec![], vec![5, 2], vec![1, 7, 3, 6, 4]]);
 }
@@ -1,65 +1,120 @@
+use std::env;
+use std::process;
 use std::vec::Vec;
 
+// The circle as a successor array: next[cup] is the cup clockwise of cup, so a
+// move only relinks three cups. Index 0 is unused.
+struct Cups {
+    next: Vec<u32>,
+    current: u32
+}
+
 fn parse_cups(input: &str) -> Vec<u32> {
     input.chars().map(|c|
         c.to_string().parse().unwrap()
     ).collect()
 }
 
-fn cups_str(cups: &Vec<u32>) -> String {
-    let one_index = cups.iter().position(|&cup| cup == 1).unwrap();
-    let digit_strs: Vec<_> = cups[(one_index + 1)..].iter().chain(cups[0..one_index].iter()).map(|i|
-        i.to_string()
-    ).collect();
-    digit_strs.concat()
-}
+impl Cups {
+    // The labelled cups, followed by the rest up to total in order.
+    fn new(labels: &[u32], total: u32) -> Self {
+        let count = total.max(labels.len() as u32);
+        let order: Vec<u32> = labels.iter().copied().chain(labels.len() as u32 + 1..=count).collect();
+        let mut next = vec![0; count as usize + 1];
+        for (i, &cup) in order.iter().enumerate() {
+            next[cup as usize] = order[(i + 1) % order.len()];
+        }
+        Self { next, current: order[0] }
+    }
 
-fn cups_multiple(cups: &Vec<u32>) -> u64 {
-    let one_index = cups.iter().position(|&cup| cup == 1).unwrap();
-    cups[one_index + 1] as u64 * cups[one_index + 2] as u64
-}
+    fn after(&self, cup: u32) -> u32 {
+        self.next[cup as usize]
+    }
 
-// Assume first in list is current cup.
-fn do_move(cups: &mut Vec<u32>) {
-    let head: Vec<_> = cups.drain(..4).collect();
-    let current = head[0];
+    fn do_move(&mut self) {
+        let current = self.current;
+        let first = self.after(current);
+        let second = self.after(first);
+        let third = self.after(second);
 
-    let mut dest = current - 1;
-    loop {
-        if dest == 0 {
-            dest = cups.len() as u32 + 4;
+        let max = self.next.len() as u32 - 1;
+        let mut dest = current;
+        loop {
+            dest = if dest == 1 { max } else { dest - 1 };
+            if dest != first && dest != second && dest != third {
+                break;
+            }
         }
-        if head[1..].iter().any(|&n| n == dest) {
-            dest -= 1;
-            continue;
+
+        self.next[current as usize] = self.after(third);
+        self.next[third as usize] = self.after(dest);
+        self.next[dest as usize] = first;
+        self.current = self.after(current);
+    }
+
+    fn do_moves(&mut self, moves: usize) {
+        for _i in 0..moves { self.do_move() }
+    }
+
+    fn cups_str(&self) -> String {
+        let mut digit_strs = Vec::new();
+        let mut cup = self.after(1);
+        while cup != 1 {
+            digit_strs.push(cup.to_string());
+            cup = self.after(cup);
         }
-        break;
+        digit_strs.concat()
     }
 
-    let dest_index = (cups.iter().position(|&cup| cup == dest).unwrap() + 1) as usize;
-    cups.splice(dest_index..dest_index, head[1..].iter().copied());
-    cups.push(current);
+    fn cups_multiple(&self) -> u64 {
+        let first = self.after(1);
+        first as u64 * self.after(first) as u64
+    }
+}
+
+const USAGE: &str = "usage: day-23 [--cups COUNT] [--moves COUNT]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
 }
 
 fn main() {
-    let mut cups = parse_cups("925176834");
-    for _i in 0..100 { do_move(&mut cups) }
-    println!("Cups: {}", cups_str(&cups));
+    // Options: --cups and --moves size the second game.
+    let args: Vec<String> = env::args().collect();
+    let option = |name: &str| args.iter().position(|a| a == name).map(|i| match args.get(i + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(&format!("{} needs a value", name))
+    });
+    let total: u32 = option("--cups").map_or(1_000_000, |n| {
+        n.parse().unwrap_or_else(|_| usage_error(&format!("bad --cups value {:?}", n)))
+    });
+    let moves: usize = option("--moves").map_or(10_000_000, |n| {
+        n.parse().unwrap_or_else(|_| usage_error(&format!("bad --moves value {:?}", n)))
+    });
+
+    let labels = parse_cups("925176834");
+    let mut cups = Cups::new(&labels, 0);
+    cups.do_moves(100);
+    println!("Cups: {}", cups.cups_str());
+
+    let mut cups = Cups::new(&labels, total);
+    cups.do_moves(moves);
+    println!("Star cups multiple: {}", cups.cups_multiple());
 }
 
 #[test]
 fn test_rules1() {
-    let mut cups = parse_cups("389125467");
-    for _i in 0..10 { do_move(&mut cups) }
-    assert_eq!(cups_str(&cups), "92658374");
-    for _i in 10..100 { do_move(&mut cups) }
-    assert_eq!(cups_str(&cups), "67384529");
+    let mut cups = Cups::new(&parse_cups("389125467"), 0);
+    cups.do_moves(10);
+    assert_eq!(cups.cups_str(), "92658374");
+    cups.do_moves(90);
+    assert_eq!(cups.cups_str(), "67384529");
 }
 
 #[test]
 fn test_rules2() {
-    let mut cups = parse_cups("389125467");
-    cups.extend(10..=1_000_000);
-    for _i in 0..100 { do_move(&mut cups) }
-    assert_eq!(cups_multiple(&cups), 149245887792);
+    let mut cups = Cups::new(&parse_cups("389125467"), 1_000_000);
+    cups.do_moves(10_000_000);
+    assert_eq!(cups.cups_multiple(), 149245887792);
 }
@@ -1,47 +1,185 @@
 extern crate bit_vec;
 
 use bit_vec::BitVec;
-use std::vec::Vec;
+use std::collections::HashMap;
+use std::env;
 use std::fs;
+use std::io::{self, Write};
+use std::process;
+use std::vec::Vec;
 
 #[derive(Debug, PartialEq)]
-enum Exit { Repeat, OutOfBounds }
+enum Exit { Repeat, OutOfBounds, StepLimit, BadWrite }
 
-#[derive(Debug, Clone)]
-enum OpCode { Nop, Acc, Jmp }
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Reg(usize),
+    Imm(isize)
+}
 
 #[derive(Debug, Clone)]
 struct Op {
-    code: OpCode,
-    arg: isize
+    code: String,
+    args: Vec<Operand>
 }
 
 type Program = Vec<Op>;
 
-fn program_from_contents(contents: &str) -> Program {
-    let mut program = Vec::new();
-    for line in contents.lines() {
-        let tokens = line.split_whitespace().collect::<Vec<&str>>();
-        let code = match tokens[0] {
-            "nop" => OpCode::Nop,
-            "acc" => OpCode::Acc,
-            "jmp" => OpCode::Jmp,
-            _ => unreachable!()
-        };
-        let arg = tokens[1].parse::<isize>().unwrap();
-        program.push(Op { code, arg })
+// What the machine should do with the program counter once a handler returns.
+// Jump offsets are relative to the instruction that issued them. A fault is an
+// op which tried to write to an immediate.
+#[derive(Debug, PartialEq)]
+enum Flow { Next, Jump(isize), Fault }
+
+type Handler = fn(&mut Registers, &[Operand]) -> Flow;
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    UnknownOp { line: usize, code: String },
+    Arity { line: usize, expected: usize, found: usize },
+    BadOperand { line: usize, token: String },
+    RegisterExpected { line: usize, token: String }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Registers(Vec<isize>);
+
+impl Registers {
+    fn get(&self, operand: &Operand) -> isize {
+        match *operand {
+            Operand::Reg(r) => self.0[r],
+            Operand::Imm(value) => value
+        }
+    }
+    // The parser rejects immediates in the destinations an op declares, but a
+    // handler could still write to an argument it didn't declare.
+    fn update(&mut self, operand: &Operand, f: impl FnOnce(isize) -> isize) -> Flow {
+        match *operand {
+            Operand::Reg(r) => { self.0[r] = f(self.0[r]); Flow::Next }
+            Operand::Imm(_) => Flow::Fault
+        }
+    }
+}
+
+#[derive(Clone)]
+struct OpSpec {
+    arity: usize,
+    // Indices of the arguments which are written to, and so must name a register.
+    destinations: &'static [usize],
+    handler: Handler
+}
+
+// The table of opcodes and register names a program is parsed and run against.
+// Start from one of the presets and insert additional ops to extend it.
+#[derive(Clone)]
+struct InstructionSet {
+    registers: Vec<String>,
+    ops: HashMap<String, OpSpec>
+}
+
+impl InstructionSet {
+    fn new(registers: &[&str]) -> Self {
+        Self {
+            registers: registers.iter().map(|r| r.to_string()).collect(),
+            ops: HashMap::new()
+        }
     }
-    return program;
+
+    // The console from the puzzle: a single implicit accumulator register.
+    fn handheld() -> Self {
+        let mut isa = Self::new(&["acc"]);
+        isa.insert("nop", 1, &[], |_, _| Flow::Next);
+        isa.insert("acc", 1, &[], |regs, args| { regs.0[0] += regs.get(&args[0]); Flow::Next });
+        isa.insert("jmp", 1, &[], |regs, args| Flow::Jump(regs.get(&args[0])));
+        isa
+    }
+
+    // General purpose registers plus arithmetic and conditional jumps, in the
+    // style of the assembunny and duet puzzles.
+    fn extended(registers: &[&str]) -> Self {
+        let mut isa = Self::new(registers);
+        isa.insert("nop", 0, &[], |_, _| Flow::Next);
+        isa.insert("cpy", 2, &[1], |regs, args| { let v = regs.get(&args[0]); regs.update(&args[1], |_| v) });
+        isa.insert("inc", 1, &[0], |regs, args| regs.update(&args[0], |r| r + 1));
+        isa.insert("dec", 1, &[0], |regs, args| regs.update(&args[0], |r| r - 1));
+        isa.insert("add", 2, &[0], |regs, args| { let v = regs.get(&args[1]); regs.update(&args[0], |r| r + v) });
+        isa.insert("mul", 2, &[0], |regs, args| { let v = regs.get(&args[1]); regs.update(&args[0], |r| r * v) });
+        isa.insert("jmp", 1, &[], |regs, args| Flow::Jump(regs.get(&args[0])));
+        isa.insert("jnz", 2, &[], |regs, args| {
+            if regs.get(&args[0]) != 0 { Flow::Jump(regs.get(&args[1])) } else { Flow::Next }
+        });
+        isa.insert("jgz", 2, &[], |regs, args| {
+            if regs.get(&args[0]) > 0 { Flow::Jump(regs.get(&args[1])) } else { Flow::Next }
+        });
+        isa
+    }
+
+    fn insert(&mut self, code: &str, arity: usize, destinations: &'static [usize], handler: Handler) {
+        self.ops.insert(code.to_string(), OpSpec { arity, destinations, handler });
+    }
+
+    fn register(&self, name: &str) -> Option<usize> {
+        self.registers.iter().position(|r| r == name)
+    }
+
+    fn format_op(&self, op: &Op) -> String {
+        let mut tokens = vec![op.code.clone()];
+        for arg in &op.args {
+            tokens.push(match *arg {
+                Operand::Reg(r) => self.registers[r].clone(),
+                Operand::Imm(value) => format!("{:+}", value)
+            });
+        }
+        tokens.join(" ")
+    }
+
+    fn parse_op(&self, line_number: usize, line: &str) -> Result<Op, ParseError> {
+        let mut tokens = line.split_whitespace();
+        let code = tokens.next().unwrap_or("");
+        let spec = self.ops.get(code).ok_or_else(||
+            ParseError::UnknownOp { line: line_number, code: code.to_string() })?;
+
+        let mut args = Vec::new();
+        for token in tokens {
+            let operand = match self.register(token) {
+                Some(r) => Operand::Reg(r),
+                None => Operand::Imm(token.parse::<isize>().map_err(|_|
+                    ParseError::BadOperand { line: line_number, token: token.to_string() })?)
+            };
+            args.push(operand);
+        }
+        if args.len() != spec.arity {
+            return Err(ParseError::Arity { line: line_number, expected: spec.arity, found: args.len() });
+        }
+        for &d in spec.destinations {
+            if let Operand::Imm(value) = args[d] {
+                return Err(ParseError::RegisterExpected { line: line_number, token: value.to_string() });
+            }
+        }
+        Ok(Op { code: code.to_string(), args })
+    }
+}
+
+fn program_from_contents(contents: &str, isa: &InstructionSet) -> Result<Program, ParseError> {
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| isa.parse_op(index + 1, line))
+        .collect()
 }
 
+// Each program with one nop swapped for a jmp or the other way round. A swap
+// which would leave the op with the wrong number of arguments for its new
+// code, as in the extended set where nop takes none, is skipped.
 struct ProgramMutations<'a> {
     program: &'a Program,
+    isa: &'a InstructionSet,
     index: usize
 }
 
-impl<'a> ProgramMutations<'_> {
-    fn from_program(program: &'a Program) -> ProgramMutations {
-        ProgramMutations { program: program, index: 0 }
+impl<'a> ProgramMutations<'a> {
+    fn from_program(program: &'a Program, isa: &'a InstructionSet) -> ProgramMutations<'a> {
+        ProgramMutations { program, isa, index: 0 }
     }
 }
 
@@ -50,13 +188,18 @@ impl Iterator for ProgramMutations<'_> {
 
     fn next(&mut self) -> Option<Program> {
         while self.index < self.program.len() {
-            let new_code = match self.program[self.index].code {
-                OpCode::Acc => { self.index += 1; continue },
-                OpCode::Nop => OpCode::Jmp,
-                OpCode::Jmp => OpCode::Nop
+            let op = &self.program[self.index];
+            let new_code = match op.code.as_str() {
+                "nop" => "jmp",
+                "jmp" => "nop",
+                _ => { self.index += 1; continue }
             };
+            if self.isa.ops.get(new_code).is_none_or(|spec| spec.arity != op.args.len()) {
+                self.index += 1;
+                continue;
+            }
             let mut program = self.program.clone();
-            program[self.index].code = new_code;
+            program[self.index].code = new_code.to_string();
             self.index += 1;
             return Some(program);
         }
@@ -64,15 +207,32 @@ impl Iterator for ProgramMutations<'_> {
     }
 }
 
-struct Machine {
+#[derive(Debug, Clone, PartialEq)]
+struct TraceStep {
     pc: usize,
-    acc: isize
+    op: String,
+    // Register contents after the op has executed.
+    registers: Vec<isize>
 }
 
-impl Machine {
-    fn new() -> Self {
-        Self { pc: 0, acc: 0 }
+struct Machine<'a> {
+    isa: &'a InstructionSet,
+    pc: usize,
+    registers: Registers,
+    trace: Option<Vec<TraceStep>>
+}
+
+impl<'a> Machine<'a> {
+    fn new(isa: &'a InstructionSet) -> Self {
+        Self { isa, pc: 0, registers: Registers(vec![0; isa.registers.len()]), trace: None }
+    }
+    fn with_trace(isa: &'a InstructionSet) -> Self {
+        Self { trace: Some(Vec::new()), ..Self::new(isa) }
     }
+    fn acc(&self) -> isize {
+        self.registers.0[0]
+    }
+    // Run until the program leaves memory or revisits an instruction.
     fn run(&mut self, program: &Program) -> Exit {
         let mut log = BitVec::from_elem(program.len(), false);
 
@@ -84,35 +244,132 @@ impl Machine {
             }
 
             log.set(self.pc, true);
-            let op = &program[self.pc];
-            self.pc += 1;
-            match op.code {
-                OpCode::Nop => (),
-                OpCode::Acc => self.acc += op.arg,
-                OpCode::Jmp => {
-                    self.pc -= 1;
-                    if op.arg < 0 {
-                        self.pc -= -op.arg as usize
-                    } else {
-                        self.pc += op.arg as usize
+            if let Some(exit) = self.step(program) {
+                return exit;
+            }
+        }
+    }
+
+    // Run until the program leaves memory, for programs which loop on purpose.
+    fn run_for(&mut self, program: &Program, max_steps: usize) -> Exit {
+        for _ in 0..max_steps {
+            if self.pc >= program.len() {
+                return Exit::OutOfBounds
+            }
+            if let Some(exit) = self.step(program) {
+                return exit;
+            }
+        }
+        Exit::StepLimit
+    }
+
+    fn step(&mut self, program: &Program) -> Option<Exit> {
+        let op = &program[self.pc];
+        let spec = &self.isa.ops[&op.code];
+        let flow = (spec.handler)(&mut self.registers, &op.args);
+
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceStep {
+                pc: self.pc,
+                op: self.isa.format_op(op),
+                registers: self.registers.0.clone()
+            });
+        }
+
+        match flow {
+            Flow::Next => self.pc += 1,
+            Flow::Jump(offset) => {
+                if offset < 0 {
+                    // Jumping back past the start is out of bounds just
+                    // as jumping past the end is.
+                    match self.pc.checked_sub(offset.unsigned_abs()) {
+                        Some(pc) => self.pc = pc,
+                        None => return Some(Exit::OutOfBounds)
                     }
+                } else {
+                    self.pc += offset as usize
                 }
             }
+            Flow::Fault => return Some(Exit::BadWrite)
         }
+        None
     }
 }
 
+fn write_trace_csv<W: Write>(out: &mut W, isa: &InstructionSet, trace: &[TraceStep]) -> io::Result<()> {
+    writeln!(out, "step,pc,op,{}", isa.registers.join(","))?;
+    for (step, entry) in trace.iter().enumerate() {
+        let registers: Vec<String> = entry.registers.iter().map(|r| r.to_string()).collect();
+        writeln!(out, "{},{},{},{}", step, entry.pc, entry.op, registers.join(","))?;
+    }
+    Ok(())
+}
+
+const USAGE: &str = "usage: day-8 [--input FILE] [--extended REGISTERS] [--steps COUNT] [--trace FILE]";
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
+}
+
+// With --extended a,b,c the input is run once against the extended
+// instruction set, for at most --steps steps, instead of solving the puzzle.
+// --input picks a file other than input.txt, and --trace FILE writes the
+// steps of the run which finished as CSV.
 fn main() {
-    let contents = fs::read_to_string("input.txt").unwrap();
-    let program = program_from_contents(&contents);
-    let mut machine = Machine::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut input = "input.txt".to_string();
+    let mut extended = None;
+    let mut steps = 1_000_000;
+    let mut trace_filename = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || match rest.next() {
+            Some(value) => value.clone(),
+            None => usage_error(&format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--input" => input = value(),
+            "--extended" => extended = Some(value()),
+            "--steps" => {
+                let value = value();
+                steps = value.parse().unwrap_or_else(|_| usage_error(&format!("bad --steps value {:?}", value)));
+            }
+            "--trace" => trace_filename = Some(value()),
+            _ => usage_error(&format!("unknown option {:?}", arg))
+        }
+    }
+    let new_machine = |isa| if trace_filename.is_some() { Machine::with_trace(isa) } else { Machine::new(isa) };
+    let write_trace = |isa: &InstructionSet, machine: &Machine| {
+        if let Some(filename) = &trace_filename {
+            let mut file = fs::File::create(filename).unwrap();
+            write_trace_csv(&mut file, isa, machine.trace.as_ref().unwrap()).unwrap();
+        }
+    };
+
+    let contents = fs::read_to_string(&input).unwrap();
+    if let Some(registers) = extended {
+        let registers: Vec<&str> = registers.split(',').collect();
+        let isa = InstructionSet::extended(&registers);
+        let program = program_from_contents(&contents, &isa).unwrap();
+        let mut machine = new_machine(&isa);
+        let exit = machine.run_for(&program, steps);
+        println!("{:?} with registers {:?}", exit, machine.registers.0);
+        write_trace(&isa, &machine);
+        return;
+    }
+
+    let isa = InstructionSet::handheld();
+    let program = program_from_contents(&contents, &isa).unwrap();
+    let mut machine = Machine::new(&isa);
     machine.run(&program);
-    println!("Accumulator: {}", machine.acc);
+    println!("Accumulator: {}", machine.acc());
 
-    for mutated_program in ProgramMutations::from_program(&program) {
-        let mut machine = Machine::new();
+    for mutated_program in ProgramMutations::from_program(&program, &isa) {
+        let mut machine = new_machine(&isa);
         if machine.run(&mutated_program) == Exit::OutOfBounds {
-            println!("Accumulator: {}", machine.acc);
+            println!("Accumulator: {}", machine.acc());
+            write_trace(&isa, &machine);
             break;
         }
     }
@@ -130,19 +387,76 @@ acc +1
 jmp -4
 acc +6
 "#;
-    let program = program_from_contents(SAMPLE_INPUT);
-    let mut machine = Machine::new();
+    let isa = InstructionSet::handheld();
+    let program = program_from_contents(SAMPLE_INPUT, &isa).unwrap();
+    let mut machine = Machine::new(&isa);
     let exit_code = machine.run(&program);
     assert_eq!(exit_code, Exit::Repeat);
-    assert_eq!(machine.acc, 5);
+    assert_eq!(machine.acc(), 5);
 
     let mut found = false;
-    for mutated_program in ProgramMutations::from_program(&program) {
-        let mut machine = Machine::new();
+    for mutated_program in ProgramMutations::from_program(&program, &isa) {
+        let mut machine = Machine::new(&isa);
         if machine.run(&mutated_program) == Exit::OutOfBounds {
-            assert_eq!(machine.acc, 8);
+            assert_eq!(machine.acc(), 8);
             found = true;
         }
     }
     assert!(found, "Should have found an OutOfBounds mutation.");
 }
+
+#[test]
+fn test_extended() {
+    // Multiply 6 by 7 with a countdown loop.
+    const SAMPLE_INPUT: &str = r#"cpy 6 a
+cpy 7 b
+add c a
+dec b
+jnz b -2
+"#;
+    let isa = InstructionSet::extended(&["a", "b", "c"]);
+    let program = program_from_contents(SAMPLE_INPUT, &isa).unwrap();
+    let mut machine = Machine::with_trace(&isa);
+    assert_eq!(machine.run_for(&program, 100), Exit::OutOfBounds);
+    assert_eq!(machine.registers.0, vec![6, 0, 42]);
+
+    let mut csv = Vec::new();
+    let trace = machine.trace.as_ref().unwrap();
+    write_trace_csv(&mut csv, &isa, &trace[..5]).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "step,pc,op,a,b,c\n\
+        0,0,cpy +6 a,6,0,0\n\
+        1,1,cpy +7 b,6,7,0\n\
+        2,2,add c a,6,7,6\n\
+        3,3,dec b,6,6,6\n\
+        4,4,jnz b -2,6,6,6\n");
+
+    let mut machine = Machine::new(&isa);
+    assert_eq!(machine.run_for(&program, 10), Exit::StepLimit);
+
+    assert_eq!(program_from_contents("hlt", &isa).unwrap_err(),
+               ParseError::UnknownOp { line: 1, code: "hlt".to_string() });
+    assert_eq!(program_from_contents("inc 4", &isa).unwrap_err(),
+               ParseError::RegisterExpected { line: 1, token: "4".to_string() });
+    assert_eq!(program_from_contents("jnz a", &isa).unwrap_err(),
+               ParseError::Arity { line: 1, expected: 2, found: 1 });
+
+    // The extended nop takes no operands and jmp one, so neither can be
+    // swapped for the other.
+    let program = program_from_contents("nop\njmp 2\ninc a", &isa).unwrap();
+    assert_eq!(ProgramMutations::from_program(&program, &isa).count(), 0);
+}
+
+#[test]
+fn test_bad_write() {
+    // An op which writes to an argument it didn't declare as a destination.
+    let mut isa = InstructionSet::extended(&["a"]);
+    isa.insert("clr", 1, &[], |regs, args| regs.update(&args[0], |_| 0));
+    let program = program_from_contents("cpy 3 a\nclr a\nclr 3\n", &isa).unwrap();
+    let mut machine = Machine::new(&isa);
+    assert_eq!(machine.run_for(&program, 10), Exit::BadWrite);
+    assert_eq!((machine.pc, machine.registers.0.clone()), (2, vec![0]));
+
+    // Jumping back by isize::MIN is out of bounds rather than an overflow.
+    let program = program_from_contents(&format!("jmp {}", isize::MIN), &isa).unwrap();
+    assert_eq!(Machine::new(&isa).run(&program), Exit::OutOfBounds);
+}
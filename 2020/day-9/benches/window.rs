@@ -0,0 +1,20 @@
+extern crate criterion;
+extern crate day_9;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use day_9::{find_number, find_number_naive, find_run, find_run_naive, numbers_from_contents};
+use std::fs;
+
+fn bench_window(c: &mut Criterion) {
+    let contents = fs::read_to_string("input.txt").unwrap();
+    let numbers = numbers_from_contents::<u64>(&contents);
+    let target = find_number(25, &numbers).unwrap();
+
+    c.bench_function("find_number", |b| b.iter(|| find_number(black_box(25), &numbers)));
+    c.bench_function("find_number_naive", |b| b.iter(|| find_number_naive(black_box(25), &numbers)));
+    c.bench_function("find_run", |b| b.iter(|| find_run(&numbers, black_box(target))));
+    c.bench_function("find_run_naive", |b| b.iter(|| find_run_naive(&numbers, black_box(target))));
+}
+
+criterion_group!(benches, bench_window);
+criterion_main!(benches);
@@ -0,0 +1,280 @@
+extern crate itertools;
+extern crate num_traits;
+
+use itertools::Itertools;
+use num_traits::{CheckedSub, Zero};
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::io::{self, BufRead};
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+pub fn numbers_from_contents<T: FromStr>(contents: &str) -> Vec<T>
+    where T::Err: std::fmt::Debug {
+    contents.lines()
+        .map(|s| s.trim().parse::<T>().unwrap())
+        .collect()
+}
+
+// Parse one number per line from a reader, without holding the whole input.
+pub fn numbers_from_reader<T, R>(reader: R) -> impl Iterator<Item = io::Result<T>>
+    where T: FromStr, T::Err: std::fmt::Display, R: BufRead {
+    reader.lines()
+        .filter(|line| !matches!(line, Ok(l) if l.trim().is_empty()))
+        .map(|line| {
+            let line = line?;
+            line.trim().parse::<T>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {:?}", e, line)))
+        })
+}
+
+// A multiset of the values currently in the preamble window, so that each
+// candidate pair can be checked with a hash lookup rather than a rescan.
+struct Window<T> {
+    values: VecDeque<T>,
+    counts: HashMap<T, usize>
+}
+
+impl<T: Copy + Eq + Hash + CheckedSub> Window<T> {
+    fn new(preamble: &[T]) -> Self {
+        let mut window = Self { values: VecDeque::new(), counts: HashMap::new() };
+        for &value in preamble {
+            window.push(value);
+        }
+        window
+    }
+
+    fn push(&mut self, value: T) {
+        self.values.push_back(value);
+        *self.counts.entry(value).or_insert(0) += 1;
+    }
+
+    fn pop(&mut self) {
+        let value = self.values.pop_front().unwrap();
+        let count = self.counts.get_mut(&value).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.counts.remove(&value);
+        }
+    }
+
+    // Whether two values at different positions in the window add to target.
+    fn has_pair(&self, target: T) -> bool {
+        self.counts.iter().any(|(&value, &count)| {
+            match target.checked_sub(&value) {
+                Some(other) if other == value => count > 1,
+                Some(other) => self.counts.contains_key(&other),
+                None => false
+            }
+        })
+    }
+}
+
+// O(n·p) in the window length, against O(n·p²) for find_number_naive.
+pub fn find_number<T>(preamble_len: usize, numbers: &[T]) -> Option<T>
+    where T: Copy + Eq + Hash + CheckedSub {
+    if numbers.len() < preamble_len {
+        return None;
+    }
+    let mut window = Window::new(&numbers[..preamble_len]);
+    for &value in &numbers[preamble_len..] {
+        if !window.has_pair(value) {
+            return Some(value);
+        }
+        window.pop();
+        window.push(value);
+    }
+    None
+}
+
+// Two-pointer scan for the first run of at least two contiguous values adding
+// to target_sum. The window only ever shrinks from the front, so this needs
+// non-negative inputs.
+pub fn find_run<T>(all: &[T], target_sum: T) -> Option<&[T]>
+    where T: Copy + Ord + Zero + Add<Output = T> + Sub<Output = T> {
+    let mut start = 0;
+    let mut sum = T::zero();
+    for (end, &value) in all.iter().enumerate() {
+        sum = sum + value;
+        while sum > target_sum && start + 1 < end {
+            sum = sum - all[start];
+            start += 1;
+        }
+        if sum == target_sum && start < end {
+            return Some(&all[start..(end + 1)]);
+        }
+    }
+    None
+}
+
+// Validates a stream one number at a time, reporting each number which is not
+// a sum of two of the preamble_len before it. Invalid numbers still enter the
+// window, so that every later number is judged against its true predecessors.
+pub struct Validator<T> {
+    preamble_len: usize,
+    window: Window<T>,
+    position: usize
+}
+
+impl<T: Copy + Eq + Hash + CheckedSub> Validator<T> {
+    pub fn new(preamble_len: usize) -> Self {
+        Self { preamble_len, window: Window::new(&[]), position: 0 }
+    }
+
+    // Returns false if the value is invalid; values in the preamble are always valid.
+    // A zero preamble keeps the window empty, so as with find_number every
+    // value is invalid.
+    pub fn push(&mut self, value: T) -> bool {
+        let valid = self.position < self.preamble_len || self.window.has_pair(value);
+        if self.preamble_len > 0 {
+            if self.position >= self.preamble_len {
+                self.window.pop();
+            }
+            self.window.push(value);
+        }
+        self.position += 1;
+        valid
+    }
+}
+
+pub struct InvalidNumbers<T, I> {
+    validator: Validator<T>,
+    numbers: std::iter::Enumerate<I>
+}
+
+impl<T, I> Iterator for InvalidNumbers<T, I>
+    where T: Copy + Eq + Hash + CheckedSub, I: Iterator<Item = T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<(usize, T)> {
+        let validator = &mut self.validator;
+        self.numbers.find(|&(_, value)| !validator.push(value))
+    }
+}
+
+// Every invalid number in the stream, with its zero-based position.
+pub fn invalid_numbers<T, I>(preamble_len: usize, numbers: I) -> InvalidNumbers<T, I::IntoIter>
+    where T: Copy + Eq + Hash + CheckedSub, I: IntoIterator<Item = T> {
+    InvalidNumbers { validator: Validator::new(preamble_len), numbers: numbers.into_iter().enumerate() }
+}
+
+// The streaming counterpart to find_run: the first run of two to max_len
+// contiguous values adding to target_sum, holding no more than max_len values
+// at once. Like find_run, this needs non-negative inputs.
+pub fn find_run_in_stream<T, I>(numbers: I, target_sum: T, max_len: usize) -> Option<Vec<T>>
+    where T: Copy + Ord + Zero + Add<Output = T> + Sub<Output = T>, I: IntoIterator<Item = T> {
+    let mut run = VecDeque::with_capacity(max_len);
+    let mut sum = T::zero();
+    for value in numbers {
+        run.push_back(value);
+        sum = sum + value;
+        while run.len() > 1 && (sum > target_sum || run.len() > max_len) {
+            sum = sum - run.pop_front().unwrap();
+        }
+        if sum == target_sum && run.len() >= 2 && run.len() <= max_len {
+            return Some(run.into_iter().collect());
+        }
+    }
+    None
+}
+
+// The original implementations, kept as a reference for tests and benchmarks.
+pub fn find_number_naive(preamble_len: usize, numbers: &[u64]) -> Option<u64> {
+    let mut iter = numbers.iter();
+    let mut preamble: VecDeque<&u64> = iter.by_ref().take(preamble_len).collect();
+    for value in iter {
+        if preamble.iter().copied().combinations(2)
+            .any(|c| c.iter().copied().sum::<u64>() == *value) {
+            preamble.pop_front();
+            preamble.push_back(value);
+            continue;
+        }
+        preamble.pop_front();
+        preamble.push_back(value);
+        return Some(*value);
+    }
+    None
+}
+
+pub fn find_run_naive(all: &[u64], target_sum: u64) -> Option<&[u64]> {
+    for slice_start in 0..all.len() {
+        let inner_slice = &all[slice_start..];
+        let mut sum = 0;
+        for (count, value) in inner_slice.iter().enumerate() {
+            sum += value;
+            if sum == target_sum && count > 0 {
+                return Some(&inner_slice[..(count+1)]);
+            } else if sum > target_sum {
+                break;
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn test_matches_naive() {
+    // Small pseudo-random values so that there are plenty of invalid numbers
+    // and repeated values in each window.
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(12345);
+    let numbers: Vec<u64> = (0..400).map(|_| rng.gen_range(0..40)).collect();
+
+    for preamble_len in 2..8 {
+        for start in 0..50 {
+            let slice = &numbers[start..];
+            assert_eq!(find_number(preamble_len, slice), find_number_naive(preamble_len, slice));
+        }
+    }
+    for target in 0..200 {
+        assert_eq!(find_run(&numbers, target), find_run_naive(&numbers, target));
+        assert_eq!(find_run(&numbers, target).map(|run| run.to_vec()),
+                   find_run_in_stream(numbers.iter().copied(), target, numbers.len()));
+    }
+}
+
+#[test]
+fn test_signed() {
+    let numbers = [3i32, -1, 4, 2, 3, -7];
+    assert_eq!(find_number(3, &numbers), Some(-7));
+    assert_eq!(find_run(&[1u8, 2, 3, 4], 7), Some(&[3u8, 4][..]));
+    // The target on its own isn't a run.
+    assert_eq!(find_run(&[1u8, 7, 3, 4], 7), Some(&[3u8, 4][..]));
+    assert_eq!(find_run(&[2u8, 7, 9], 7), None);
+    assert_eq!(find_run_in_stream(vec![1u8, 7, 3, 4], 7, 4), Some(vec![3, 4]));
+}
+
+#[test]
+fn test_stream() {
+    const SAMPLE_DATA: &[u8] = b"35\n20\n15\n25\n47\n40\n62\n55\n65\n95\n102\n117\n150\n182\n127\n219\n299\n277\n309\n576\n";
+
+    let numbers = numbers_from_reader::<u64, _>(SAMPLE_DATA).map(Result::unwrap);
+    let invalid: Vec<(usize, u64)> = invalid_numbers(5, numbers).collect();
+    assert_eq!(invalid, vec![(14, 127)]);
+
+    // A shorter preamble flags more numbers; check them all against the slice version.
+    let numbers = numbers_from_reader::<u64, _>(SAMPLE_DATA).map(Result::unwrap);
+    let invalid: Vec<usize> = invalid_numbers(3, numbers).map(|(i, _)| i).collect();
+    let reference = numbers_from_contents::<u64>(std::str::from_utf8(SAMPLE_DATA).unwrap());
+    let expected: Vec<usize> = (3..reference.len())
+        .filter(|&i| find_number_naive(3, &reference[(i - 3)..(i + 1)]).is_some())
+        .collect();
+    assert_eq!(invalid, expected);
+
+    let numbers = numbers_from_reader::<u64, _>(SAMPLE_DATA).map(Result::unwrap);
+    assert_eq!(find_run_in_stream(numbers, 127, 4), Some(vec![15, 25, 47, 40]));
+    let numbers = numbers_from_reader::<u64, _>(SAMPLE_DATA).map(Result::unwrap);
+    // The invalid number alone isn't a run, and no shorter run adds up to it.
+    assert_eq!(find_run_in_stream(numbers, 127, 3), None);
+    let numbers = numbers_from_reader::<u64, _>(SAMPLE_DATA).map(Result::unwrap);
+    assert_eq!(find_run_in_stream(numbers, 55, 3), Some(vec![35, 20]));
+
+    // With no preamble there are no pairs to check against.
+    let invalid: Vec<(usize, u64)> = invalid_numbers(0, vec![1, 2, 3]).collect();
+    assert_eq!(invalid, vec![(0, 1), (1, 2), (2, 3)]);
+    assert_eq!(find_number(0, &[1u64, 2, 3]), Some(1));
+
+    let mut bad = numbers_from_reader::<u64, _>(&b"12\nx\n"[..]);
+    assert!(bad.next().unwrap().is_ok());
+    assert_eq!(bad.next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
@@ -1,51 +1,23 @@
-extern crate itertools;
+extern crate day_9;
 
-use itertools::Itertools;
-use std::collections::VecDeque;
+use day_9::{find_number, find_run, invalid_numbers, numbers_from_contents, numbers_from_reader};
+use std::env;
 use std::fs;
+use std::io;
 
-fn numbers_from_contents(contents: &str) -> Vec<u64> {
-    contents.lines()
-        .map(|s| s.trim().parse::<u64>().unwrap())
-        .collect()
-}
-
-fn find_number(preamble_len: usize, numbers: &[u64]) -> Option<u64> {
-    let mut iter = numbers.iter();
-    let mut preamble: VecDeque<&u64> = iter.by_ref().take(preamble_len).collect();
-    for value in iter {
-        if preamble.iter().copied().combinations(2)
-            .any(|c| c.iter().copied().sum::<u64>() == *value) {
-            preamble.pop_front();
-            preamble.push_back(value);
-            continue;
-        }
-        preamble.pop_front();
-        preamble.push_back(value);
-        return Some(*value);
-    }
-    None
-}
-
-fn find_run(all: &[u64], target_sum: u64) -> Option<&[u64]> {
-    for slice_start in 0..all.len() {
-        let inner_slice = &all[slice_start..];
-        let mut sum = 0;
-        for (count, value) in inner_slice.iter().enumerate() {
-            sum += value;
-            if sum == target_sum {
-                return Some(&inner_slice[..(count+1)]);
-            } else if sum > target_sum {
-                break;
-            }
+fn main() {
+    // With "-", validate a stream from stdin and report every invalid number.
+    if env::args().nth(1).as_deref() == Some("-") {
+        let stdin = io::stdin();
+        let numbers = numbers_from_reader::<u64, _>(stdin.lock()).map(Result::unwrap);
+        for (position, value) in invalid_numbers(25, numbers) {
+            println!("Invalid: {} at line {}", value, position + 1);
         }
+        return;
     }
-    None
-}
 
-fn main() {
     let contents = fs::read_to_string("input.txt").unwrap();
-    let numbers = numbers_from_contents(&contents);
+    let numbers = numbers_from_contents::<u64>(&contents);
     let result = find_number(25, &numbers).unwrap();
     println!("Found: {}", result);
 
@@ -78,7 +50,7 @@ fn test_sample() {
     309
     576"#;
 
-    let numbers = numbers_from_contents(SAMPLE_DATA);
+    let numbers = numbers_from_contents::<u64>(SAMPLE_DATA);
     let result = find_number(5, &numbers).unwrap();
     assert_eq!(127, result);
     assert_eq!(vec![15, 25, 47, 40], find_run(&numbers, result).unwrap());